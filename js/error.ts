@@ -0,0 +1,46 @@
+import { FirebaseError } from "firebase/app";
+
+/**
+ * 同じseq_noのレコードが既にある
+ */
+export class RecordConflict extends Error {}
+
+/**
+ * 保存されたレコードが読める形になっていない
+ */
+export class MalformedRecord extends Error {}
+
+/**
+ * Rust側のBridgeErrorに対応する種類
+ */
+export type BridgeErrorKind = "PERMISSION_DENIED" | "NOT_FOUND" | "NETWORK" | "CONFLICT" | "QUOTA" | "MALFORMED" | "UNEXPECTED";
+
+export type OnError = (kind: BridgeErrorKind) => void;
+
+export const toBridgeError = (e: unknown): BridgeErrorKind => {
+    if (e instanceof RecordConflict) {
+        return "CONFLICT";
+    }
+    if (e instanceof MalformedRecord) {
+        return "MALFORMED";
+    }
+    if (e instanceof FirebaseError) {
+        switch (e.code) {
+            case "permission-denied":
+            case "unauthenticated":
+                return "PERMISSION_DENIED";
+            case "not-found":
+                return "NOT_FOUND";
+            case "unavailable":
+            case "deadline-exceeded":
+            // トランザクションが混み合って諦めただけで、seq_noが使われたとは限らない
+            case "aborted":
+                return "NETWORK";
+            case "already-exists":
+                return "CONFLICT";
+            case "resource-exhausted":
+                return "QUOTA";
+        }
+    }
+    return "UNEXPECTED";
+}
@@ -1,38 +1,61 @@
-import * as member from "./member";
 import * as room from "./room";
 import * as record from "./record";
 import {getYourId as getYourIdInner} from "./yourid";
+import * as notebook from "./notebook";
+import * as snapshot from "./snapshot";
+import * as share from "./share";
+import * as local from "./local";
+import * as server from "./server";
+import { OnError, toBridgeError } from "./error";
 
-export function syncMember(roomid:string,callback: (json:string) => void,onError: () => void) : () => void {
-    return member.syncMember(roomid,callback,onError);
+export async function createRoom(roomId:string,encoding:string,callback:(callback:string) => void,onError: OnError) {
+    room.createRoom(roomId,encoding).then(() => callback(roomId)).catch(e => onError(toBridgeError(e)))
 }
 
-export function registerMember(roomid:string,name:string,onError: () => void) {
-    member.registerMember(roomid,name).catch(onError);
+export function syncRoom(roomId:string,callback:(id:string | null) => void,onError: OnError) : () => void {
+    return room.syncRoom(roomId,callback,onError)
 }
 
-export function fetchMembers(roomid:string,callback: (json:string) => void,onError: () => void) {
-    member.fetchMembers(roomid,callback,onError);
+export const pushRecord = (roomId:string,recordId:string,author:string,at:number | undefined,commandJson:string | undefined,resultJson:string | undefined,payload:string | undefined,seqNo:number,checksum:string,version:number,snapshot:boolean,onError: OnError)=> {
+    const body: record.RecordBody = payload !== undefined ?
+        { payload } :
+        { command: commandJson ?? "", result: resultJson ?? "" };
+    return record.pushRecord(roomId,recordId,author,at,body,seqNo,checksum,version,snapshot)
+        .catch(e => onError(toBridgeError(e)));
 }
 
-export async function createRoom(roomId:string,hostUserName:string,callback:(callback:string) => void,onError: () => void) {
-    room.createRoom(roomId,hostUserName).then(() => callback(roomId)).catch(onError)
+export const syncRecordUpdate = (roomId:string,listener: (json:string) => void,onError: OnError) : () => void => {
+    return record.syncRecordUpdate(roomId,listener,onError);
 }
 
-export function syncRoom(roomId:string,callback:(id:string | null) => void,onError: () => void) : () => void {
-    return room.syncRoom(roomId,callback,onError)
+export const fetchRecords = (roomId:string,fromSeqNo:number,callback: (json:string) => void,onError: OnError) => {
+    record.fetchRecords(roomId,fromSeqNo).then(callback).catch(e => onError(toBridgeError(e)));
 }
 
-export function startRoom(roomId:string,onError: () => void) {
-    room.startRoom(roomId).catch(onError);
-}
+export const getYourId = (roomid: string) => getYourIdInner(roomid)
+
+export const getNotebook = (roomId: string) => notebook.getNotebook(roomId)
+
+export const setNotebook = (roomId: string,json: string) => notebook.setNotebook(roomId,json)
 
-export const pushRecord = (roomId:string,recordId:string,commandJson:string,resultJson:string,onError: () => void)=> {
-    return record.pushRecord(roomId,recordId,commandJson,resultJson).catch(onError);
+export const getVerifiedSnapshot = (roomId: string) => snapshot.getVerifiedSnapshot(roomId)
+
+export const setVerifiedSnapshot = (roomId: string,checksum: string) => snapshot.setVerifiedSnapshot(roomId,checksum)
+
+export const shareSvg = (fileName: string,svg: string,onError: OnError) => {
+    share.shareSvg(fileName,svg).catch(e => onError(toBridgeError(e)));
 }
 
-export const syncRecordUpdate = (roomId:string,listener: (json:string) => void,onError: () => void) : () => void => {
-    return record.syncRecordUpdate(roomId,listener,onError);
+export const getLocalRecords = (roomId: string) => local.getLocalRecords(roomId)
+
+export const setLocalRecords = (roomId: string,json: string) => local.setLocalRecords(roomId,json)
+
+export const clearLocalRecords = (roomId: string) => local.clearLocalRecords(roomId)
+
+export const syncServerRecord = (url: string,roomId: string,listener: (json:string) => void,onError: OnError) : () => void => {
+    return server.syncServerRecord(url,roomId,listener,onError);
 }
 
-export const getYourId = (roomid: string) => getYourIdInner(roomid) 
\ No newline at end of file
+export const pushServerCommand = (roomId: string,recordId: string,commandJson: string,onError: OnError) => {
+    server.pushServerCommand(roomId,recordId,commandJson,onError);
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+const toRecordsKey = (roomId: string) => `${roomId}:records`;
+export const getLocalRecords = (roomId:string) => window.localStorage.getItem(toRecordsKey(roomId));
+
+export const setLocalRecords = (roomId:string,json: string) => window.localStorage.setItem(toRecordsKey(roomId),json);
+
+export const clearLocalRecords = (roomId:string) => window.localStorage.removeItem(toRecordsKey(roomId));
@@ -0,0 +1,4 @@
+const toNotebookKey = (roomId: string) => `${roomId}:notebook`;
+export const getNotebook = (roomId:string) => window.localStorage.getItem(toNotebookKey(roomId));
+
+export const setNotebook = (roomId:string,json: string) => window.localStorage.setItem(toNotebookKey(roomId),json);
@@ -1,46 +1,125 @@
-import { collection, doc, onSnapshot,runTransaction,getDocs,orderBy,query } from "firebase/firestore";
+import { collection, doc, onSnapshot,runTransaction,orderBy,query,getDoc,getDocs,where,DocumentData,Bytes } from "firebase/firestore";
 import { getStore } from "./firestore";
+import { MalformedRecord, OnError, RecordConflict, toBridgeError } from "./error";
 
-export const pushRecord = (roomId:string,recordId:string,commandJson:string,resultJson:string) : Promise<void> => {
+/**
+ * commandとresultのJSONか、まとめて圧縮したもののbase64
+ */
+export type RecordBody = { command: string, result: string } | { payload: string };
+
+/**
+ * seq_noをドキュメントのidにして、まだ無いときだけ書き込む
+ * スナップショットなら、部屋に最新のseq_noとして残す
+ */
+export const pushRecord = (roomId:string,recordId:string,author:string,at:number | undefined,body:RecordBody,seqNo:number,checksum:string,version:number,snapshot:boolean) : Promise<void> => {
     const db = getStore();
     const rooms = collection(db,"rooms");
     const room = doc(rooms,roomId);
     const records = collection(room,"records");
-    const newRecordRef = doc(records)
+    const newRecordRef = doc(records,String(seqNo))
     return runTransaction(db,async (t) => {
-        const recordsData = await getDocs(records);
-        const size = recordsData.size;
-        await t.set(newRecordRef,{
+        const existing = await t.get(newRecordRef);
+        if (existing.exists()) {
+            throw new RecordConflict();
+        }
+        const content = "payload" in body ?
+            { payload: Bytes.fromBase64String(body.payload) } :
+            { command: body.command, result: body.result };
+        t.set(newRecordRef,{
             id:recordId,
-            command:commandJson,
-            result: resultJson,
-            seq_no: size
+            author,
+            // 時刻の無いコマンドはフィールドごと書かない
+            ...(at !== undefined ? { at } : {}),
+            ...content,
+            seq_no: seqNo,
+            checksum,
+            version
         })
+        if (snapshot) {
+            t.update(room,{latest_snapshot: seqNo});
+        }
     });
 }
 
-export const syncRecordUpdate = (roomId:string,callback: (recordsJson:string) => void,onError: () => void) : () => void => {
+const parseJson = (json: unknown) => {
+    try {
+        return JSON.parse(json as string);
+    } catch {
+        throw new MalformedRecord();
+    }
+};
+
+/**
+ * commandやresultがJSONでなければMalformedRecordを投げる
+ */
+const toRecordObj = (data: DocumentData) => data.payload instanceof Bytes ? {
+    id: data.id,
+    seq_no: data.seq_no,
+    checksum: data.checksum,
+    payload: data.payload.toBase64()
+} : {
+    id: data.id,
+    seq_no: data.seq_no,
+    checksum: data.checksum,
+    // 無いのは最初の形式。読むときにRust側で今の形式に上げる
+    version: data.version,
+    command: {
+        author: data.author,
+        at: data.at,
+        command: parseJson(data.command)
+    },
+    result: parseJson(data.result)
+};
+
+export const syncRecordUpdate = (roomId:string,callback: (recordsJson:string) => void,onError: OnError) : () => void => {
     const db = getStore();
     const rooms = collection(db,"rooms");
     const room = doc(rooms,roomId);
     const records = collection(room,"records");
-    const orderedRecord = query(records,orderBy("seq_no"));
-    return onSnapshot(
-        orderedRecord,
-        (snapshot) => {
-            const recordsObj = snapshot
-                .docChanges()
-                .filter(change => change.type === "added")
-                .map(change => {
-                    const data = change.doc.data();
-                    return {
-                        id: data.id,
-                        command: JSON.parse(data.command),
-                        result: JSON.parse(data.result)
+    let unsubscribe: (() => void) | null = null;
+    let closed = false;
+    // 最新のスナップショットから読み込む
+    getDoc(room)
+        .then(data => {
+            if (closed) {
+                return;
+            }
+            const from: number = data.data()?.latest_snapshot ?? 0;
+            const orderedRecord = query(records,where("seq_no",">=",from),orderBy("seq_no"));
+            unsubscribe = onSnapshot(
+                orderedRecord,
+                (snapshot) => {
+                    // ここで投げるとonSnapshotの中で握りつぶされるので、onErrorに渡す
+                    let recordsObj;
+                    try {
+                        recordsObj = snapshot
+                            .docChanges()
+                            .filter(change => change.type === "added")
+                            .map(change => toRecordObj(change.doc.data()));
+                    } catch (e) {
+                        onError(toBridgeError(e));
+                        return;
                     }
-                });
-            callback(JSON.stringify(recordsObj));
-        },
-        onError
-    )
+                    callback(JSON.stringify(recordsObj));
+                },
+                e => onError(toBridgeError(e))
+            )
+        })
+        .catch(e => onError(toBridgeError(e)));
+    return () => {
+        closed = true;
+        unsubscribe?.();
+    }
+}
+
+/**
+ * 抜けたレコードを取り直す
+ */
+export const fetchRecords = async (roomId:string,fromSeqNo:number) : Promise<string> => {
+    const db = getStore();
+    const rooms = collection(db,"rooms");
+    const room = doc(rooms,roomId);
+    const records = collection(room,"records");
+    const snapshot = await getDocs(query(records,where("seq_no",">=",fromSeqNo),orderBy("seq_no")));
+    return JSON.stringify(snapshot.docs.map(doc => toRecordObj(doc.data())));
 }
\ No newline at end of file
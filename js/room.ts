@@ -1,60 +1,35 @@
-import { collection,doc,onSnapshot,setDoc,runTransaction, writeBatch } from "firebase/firestore";
+import { collection,doc,onSnapshot,serverTimestamp,setDoc } from "firebase/firestore";
 import { getStore } from "./firestore";
-import { getYourId, setYourId } from "./yourid";
+import { OnError, toBridgeError } from "./error";
 
 /**
- * FIXME: バッチのせいで色々ごちゃってる
+ * 参加や開始はレコードとして書き込むので、部屋には保存形式だけを残す
  * @param roomId 
- * @param hostUserName 
+ * @param encoding レコードの保存形式 "json" | "compact"
  */
-export const createRoom = async (roomId:string,hostUserName:string):Promise<void> => {
+export const createRoom = async (roomId:string,encoding:string):Promise<void> => {
     const db = getStore();
     const rooms = collection(db,"rooms");
     const room = doc(rooms,roomId);
-    const members = collection(room,"members");
-    const hostMember = doc(members);
-    const batch = writeBatch(db);
-    batch.set(hostMember,{
-        name: hostUserName
+    await setDoc(room, {
+        encoding,
+        created_at: serverTimestamp()
     });
-    batch.set(room, {
-        phase:"MEETING",
-        host: hostMember.id
-    })
-    // commit こけた時のハンドリング 本音を言うとコミット成功判定とローカルDB書き換えの間に実行したいぽよ
-    setYourId(roomId,hostMember.id);
-    await batch.commit();
 }
 
-export const syncRoom = (roomId:string,callback:(roomData:string | null) => void,onError: () => void): () => void => {
+export const syncRoom = (roomId:string,callback:(roomData:string | null) => void,onError: OnError): () => void => {
     const db = getStore();
     const rooms = collection(db,"rooms");
     const room = doc(rooms,roomId);
     return onSnapshot(
         room, 
         (data) => {
-            const yourId = getYourId(roomId);
             const room: string | null = data.exists() ? 
                 JSON.stringify({ 
-                    phase: data.data().phase,
-                    is_host: data.data().host === yourId
+                    encoding: data.data().encoding ?? "json"
                 }) : null
             callback(room);
         },
-        onError
+        e => onError(toBridgeError(e))
     )
 }
-
-export const startRoom = (roomId:string) : Promise<void> => {
-    const db = getStore();
-    const rooms = collection(db,"rooms");
-    const room = doc(rooms,roomId);
-    return runTransaction(db,async (t) => {
-        const data = await t.get(room);
-        const newData = {
-            ...data.data(),
-            phase:"STARTED"
-        }
-        await t.set(room,newData);
-    })
-}
\ No newline at end of file
@@ -0,0 +1,41 @@
+import { OnError } from "./error";
+
+type Connection = {
+    socket: WebSocket,
+    // 接続する前に送られたコマンド
+    queue: string[]
+};
+
+const connections = new Map<string,Connection>();
+
+export const syncServerRecord = (url:string,roomId:string,callback: (recordsJson:string) => void,onError: OnError) : () => void => {
+    const socket = new WebSocket(`${url}/${encodeURIComponent(roomId)}`);
+    const connection = {socket,queue:[]};
+    connections.set(roomId,connection);
+    socket.onopen = () => connection.queue.splice(0).forEach(message => socket.send(message));
+    socket.onmessage = (event) => callback(event.data);
+    socket.onerror = () => onError("NETWORK");
+    return () => {
+        connections.delete(roomId);
+        socket.close();
+    }
+}
+
+export const pushServerCommand = (roomId:string,recordId:string,commandJson:string,onError: OnError) => {
+    const connection = connections.get(roomId);
+    if (connection === undefined) {
+        onError("UNEXPECTED");
+        return;
+    }
+    const message = `{"id":${JSON.stringify(recordId)},"command":${commandJson}}`;
+    switch (connection.socket.readyState) {
+        case WebSocket.CONNECTING:
+            connection.queue.push(message);
+            break;
+        case WebSocket.OPEN:
+            connection.socket.send(message);
+            break;
+        default:
+            onError("NETWORK");
+    }
+}
@@ -0,0 +1,19 @@
+export const shareSvg = (fileName:string,svg:string) : Promise<void> => {
+    const file = new File([svg],fileName,{type:"image/svg+xml"});
+    if(navigator.canShare && navigator.canShare({files:[file]})){
+        return navigator.share({files:[file]}).catch(e => {
+            // ユーザーがキャンセルした場合
+            if(e instanceof DOMException && e.name === "AbortError"){
+                return
+            }
+            throw e
+        })
+    }
+    const url = URL.createObjectURL(file);
+    const anchor = document.createElement("a");
+    anchor.href = url;
+    anchor.download = fileName;
+    anchor.click();
+    URL.revokeObjectURL(url);
+    return Promise.resolve();
+}
@@ -0,0 +1,8 @@
+const toSnapshotKey = (roomId: string) => `${roomId}:verified_snapshot`;
+
+/**
+ * 最初から辿って確かめたスナップショットの状態のchecksum
+ */
+export const getVerifiedSnapshot = (roomId:string) => window.localStorage.getItem(toSnapshotKey(roomId));
+
+export const setVerifiedSnapshot = (roomId:string,checksum: string) => window.localStorage.setItem(toSnapshotKey(roomId),checksum);
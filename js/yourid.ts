@@ -1,5 +1,18 @@
+import { collection,doc } from "firebase/firestore";
+import { getStore } from "./firestore";
 
 const toYourIdKey = (roomId: string) => `${roomId}:yourid`;
-export const getYourId = (roomId:string) => window.localStorage.getItem(toYourIdKey(roomId));
 
-export const setYourId = (roomId:string,yourId: string) => window.localStorage.setItem(toYourIdKey(roomId),yourId);
\ No newline at end of file
+/**
+ * その部屋での自分のid。初めて開いた部屋ではFirestoreの自動IDで作って覚えておく
+ */
+export const getYourId = (roomId:string): string => {
+    const key = toYourIdKey(roomId);
+    const yourId = window.localStorage.getItem(key);
+    if (yourId !== null) {
+        return yourId;
+    }
+    const created = doc(collection(getStore(),"rooms",roomId,"members")).id;
+    window.localStorage.setItem(key,created);
+    return created;
+}
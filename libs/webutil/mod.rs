@@ -1,5 +1,5 @@
 pub mod window {
-    use js_sys::{Array, Function};
+    use js_sys::{Array, Date, Function};
     use mytil::{Cleanable, Cleaner};
     use wasm_bindgen::prelude::*;
     use web_sys::{Window, window};
@@ -15,6 +15,11 @@ pub mod window {
         }.into()
     }
 
+    /// エポックからのミリ秒
+    pub fn now() -> f64 {
+        Date::now()
+    }
+
     pub struct ClearTimeout {
         window: Window,
         id: i32
@@ -0,0 +1,157 @@
+use domain::{
+    function::InitPlayer,
+    model::{Ability, HintId, PlayerId, Role, TradeId},
+    state::AppCommand,
+};
+
+pub const HELP: &str = "\
+password <p> <あいことば> <ヒント>...   あいことばとヒントを入力する
+auto                                   入力していない人のあいことばとヒントを埋める
+roles [detective|decoy|guardian]...    ホストとして配る役職を決める
+guess <p> <suspect>                    pがsuspectをターゲットだと推理する
+investigate <p>                        Detectiveの能力を使う
+guard <p> <target>                     Guardianの能力を使う
+trade <from> <to> <offer> <request>    ヒントの交換を申し込む
+accept <p> <trade> / decline <p> <trade>
+view [p]                               見えている盤面を表示する。省略すると全員分
+help / quit";
+
+/// 端末から入力される操作
+#[derive(Debug, Clone)]
+pub enum Input {
+    /// 操作するプレイヤーとコマンド
+    Dispatch(PlayerId, AppCommand),
+    Auto,
+    View(Option<PlayerId>),
+    Help,
+    Quit,
+}
+
+fn player(word: Option<&str>) -> Result<PlayerId, String> {
+    number(word).map(PlayerId)
+}
+
+fn role(word: &str) -> Result<Role, String> {
+    match word {
+        "detective" => Ok(Role::Detective),
+        "decoy" => Ok(Role::Decoy),
+        "guardian" => Ok(Role::Guardian),
+        word => Err(format!("知らない役職です: {}", word)),
+    }
+}
+
+fn number(word: Option<&str>) -> Result<usize, String> {
+    let word = word.ok_or_else(|| "引数が足りません".to_string())?;
+    word.parse().map_err(|_| format!("数字ではありません: {}", word))
+}
+
+pub fn parse(line: &str) -> Result<Input, String> {
+    let mut words = line.split_whitespace();
+    let name = match words.next() {
+        Some(name) => name,
+        None => return Ok(Input::View(None)),
+    };
+    let input = match name {
+        "password" => {
+            let id = player(words.next())?;
+            let password = words.next().ok_or_else(|| "あいことばがありません".to_string())?.to_string();
+            let hints = words.by_ref().map(str::to_string).collect();
+            Input::Dispatch(id.clone(), AppCommand::PushPassword(InitPlayer { id, password, hints }))
+        }
+        "auto" => Input::Auto,
+        // 0番の端末がホスト
+        "roles" => Input::Dispatch(PlayerId(0), AppCommand::ChooseRoles(words.by_ref().map(role).collect::<Result<_, _>>()?)),
+        "guess" => {
+            let guesser = player(words.next())?;
+            let suspect = player(words.next())?;
+            Input::Dispatch(guesser.clone(), AppCommand::Guess(guesser, suspect))
+        }
+        "investigate" => {
+            let id = player(words.next())?;
+            Input::Dispatch(id.clone(), AppCommand::UseAbility(id, Ability::Investigate))
+        }
+        "guard" => {
+            let id = player(words.next())?;
+            let target = player(words.next())?;
+            Input::Dispatch(id.clone(), AppCommand::UseAbility(id, Ability::Guard(target)))
+        }
+        "trade" => {
+            let from = player(words.next())?;
+            let to = player(words.next())?;
+            let offer = HintId(number(words.next())?);
+            let request = HintId(number(words.next())?);
+            Input::Dispatch(from.clone(), AppCommand::ProposeTrade { from, to, offer, request })
+        }
+        "accept" | "decline" => {
+            let id = player(words.next())?;
+            let trade = TradeId(number(words.next())?);
+            let command = if name == "accept" {
+                AppCommand::AcceptTrade(id.clone(), trade)
+            } else {
+                AppCommand::DeclineTrade(id.clone(), trade)
+            };
+            Input::Dispatch(id, command)
+        }
+        "view" => Input::View(words.next().map(|word| player(Some(word))).transpose()?),
+        "help" => Input::Help,
+        "quit" | "exit" => Input::Quit,
+        name => return Err(format!("知らないコマンドです: {}", name)),
+    };
+    match words.next() {
+        Some(rest) => Err(format!("余分な引数があります: {}", rest)),
+        None => Ok(input),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use domain::{
+        model::{Ability, HintId, PlayerId, Role},
+        state::AppCommand,
+    };
+
+    use super::{parse, Input};
+
+    #[test]
+    fn test_parse_password() {
+        match parse("password 1 りんご 赤い 丸い").unwrap() {
+            Input::Dispatch(PlayerId(1), AppCommand::PushPassword(input)) => {
+                assert_eq!(input.id, PlayerId(1));
+                assert_eq!(input.password, "りんご");
+                assert_eq!(input.hints, vec!["赤い".to_string(), "丸い".to_string()]);
+            }
+            input => panic!("{:?}", input),
+        }
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert!(matches!(
+            parse("  guess 0 2 ").unwrap(),
+            Input::Dispatch(PlayerId(0), AppCommand::Guess(PlayerId(0), PlayerId(2)))
+        ));
+        assert!(matches!(
+            parse("guard 1 3").unwrap(),
+            Input::Dispatch(PlayerId(1), AppCommand::UseAbility(PlayerId(1), Ability::Guard(PlayerId(3))))
+        ));
+        assert!(matches!(
+            parse("trade 0 1 4 7").unwrap(),
+            Input::Dispatch(PlayerId(0), AppCommand::ProposeTrade { offer: HintId(4), request: HintId(7), .. })
+        ));
+        match parse("roles detective decoy").unwrap() {
+            Input::Dispatch(PlayerId(0), AppCommand::ChooseRoles(roles)) => assert_eq!(roles, vec![Role::Detective, Role::Decoy]),
+            input => panic!("{:?}", input),
+        }
+        assert!(matches!(parse("").unwrap(), Input::View(None)));
+        assert!(matches!(parse("view 2").unwrap(), Input::View(Some(PlayerId(2)))));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("guess 0").is_err());
+        assert!(parse("guess 0 x").is_err());
+        assert!(parse("guess 0 1 2").is_err());
+        assert!(parse("dance").is_err());
+        assert!(parse("roles wizard").is_err());
+    }
+}
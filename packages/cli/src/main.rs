@@ -0,0 +1,160 @@
+use std::{
+    cell::RefCell,
+    convert::Infallible,
+    env,
+    io::{self, BufRead, Write},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use domain::{
+    function::InitPlayer,
+    memory::{MemoryLog, MemoryRepository},
+    model::PlayerId,
+    profile::{PlayerProfile, Profiles},
+    state::{AppCommand, AppCore, AppResult, AppState, AuthoredCommand},
+};
+use exprocess::client::Runner;
+
+mod command;
+mod view;
+
+use command::{parse, Input, HELP};
+use view::project;
+
+/// プレイヤーごとの端末。同じログを共有する
+struct Client {
+    member_id: String,
+    runner: Runner<AppCore, Infallible>,
+    state: Rc<RefCell<AppState>>,
+}
+
+impl Client {
+    fn dispatch(&mut self, command: AppCommand) {
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|elapsed| elapsed.as_millis() as u64);
+        self.runner.dispatch(AuthoredCommand { at, ..AuthoredCommand::new(self.member_id.clone(), command) })
+    }
+}
+
+fn member_id(n: usize) -> String {
+    format!("member-{}", n)
+}
+
+fn connect(log: &MemoryLog<AppCore>, member_id: String, results: Option<Rc<RefCell<Vec<AppResult>>>>) -> Client {
+    let state = Rc::new(RefCell::new(AppState::Blank));
+    let listener_state = state.clone();
+    let runner = Runner::start(
+        MemoryRepository::new(log),
+        Box::new(move |new_results, app: &AppState| {
+            listener_state.replace(app.clone());
+            if let Some(results) = &results {
+                results.borrow_mut().extend(new_results.into_iter().cloned());
+            }
+        }),
+        Box::new(|err: Infallible| match err {}),
+    );
+    Client { member_id, runner, state }
+}
+
+fn report(result: &AppResult) {
+    match result {
+        AppResult::Rejected(reason) => println!("受け付けられませんでした: {:?}", reason),
+        AppResult::InitProfile(_) => println!("参加者を登録しました"),
+        AppResult::PushPassword(input) => println!("{}があいことばを入力しました", input.id.0),
+        AppResult::InitBoard(_) => println!("ゲームが始まりました"),
+        AppResult::UseAbility(id, effect) => println!("{}が能力を使いました: {:?}", id.0, effect),
+        AppResult::Guess(guess) => println!("{}が{}を推理しました: {:?}", guess.guesser.0, guess.suspect.0, guess.outcome),
+        AppResult::Trade(event) => println!("交換: {:?}", event),
+        AppResult::Snapshot(_) => println!("スナップショットを残しました"),
+        AppResult::Joined(member) => println!("{}が参加しました", member.name),
+        AppResult::Left(member_id) => println!("{}が抜けました", member_id),
+        AppResult::ChooseRoles(roles) => println!("配る役職: {:?}", roles),
+    }
+}
+
+/// まだ入力していない人のあいことばとヒントを埋める
+fn auto_passwords(state: &AppState) -> Vec<AppCommand> {
+    match state {
+        AppState::StandbyPassword(profiles, inputs, setting) => {
+            let mut ids: Vec<&PlayerId> = profiles.players.values().map(|profile| &profile.id).collect();
+            ids.sort_by_key(|id| id.0);
+            ids.into_iter()
+                .filter(|id| !inputs.iter().any(|input| &input.id == *id))
+                .map(|id| {
+                    AppCommand::PushPassword(InitPlayer {
+                        id: id.clone(),
+                        password: format!("pass-{}", id.0),
+                        hints: (0..setting.hints_num).map(|n| format!("hint-{}-{}", id.0, n)).collect(),
+                    })
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn main() {
+    let players: usize = match env::args().nth(1).map(|arg| arg.parse()) {
+        None => 4,
+        Some(Ok(players)) => players,
+        Some(Err(_)) => {
+            eprintln!("usage: find-meee-cli [players]");
+            std::process::exit(2);
+        }
+    };
+    let log = MemoryLog::<AppCore>::new();
+    let results = Rc::new(RefCell::new(Vec::new()));
+    // 0番の端末に届いた結果を表示する
+    let mut clients: Vec<Client> = (0..players)
+        .map(|n| connect(&log, member_id(n), if n == 0 { Some(results.clone()) } else { None }))
+        .collect();
+    if let Some(client) = clients.first_mut() {
+        client.dispatch(AppCommand::InitProfile(Profiles {
+            players: (0..players)
+                .map(|n| (member_id(n), PlayerProfile { id: PlayerId(n), display_name: format!("player-{}", n) }))
+                .collect(),
+            host: member_id(0),
+        }));
+    }
+    println!("{}", HELP);
+    let stdin = io::stdin();
+    loop {
+        for result in results.borrow_mut().drain(..) {
+            report(&result);
+        }
+        print!("> ");
+        io::stdout().flush().ok();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        match parse(line.as_str()) {
+            Ok(Input::Dispatch(id, command)) => match clients.get_mut(id.0) {
+                Some(client) => client.dispatch(command),
+                None => println!("{}番の端末はありません", id.0),
+            },
+            Ok(Input::Auto) => {
+                let commands = clients.first().map_or(Vec::new(), |client| auto_passwords(&client.state.borrow()));
+                for command in commands {
+                    if let AppCommand::PushPassword(input) = &command {
+                        let id = input.id.0;
+                        clients[id].dispatch(command);
+                    }
+                }
+            }
+            Ok(Input::View(Some(id))) => match clients.get(id.0) {
+                Some(client) => print!("{}", project(&client.state.borrow(), &id)),
+                None => println!("{}番の端末はありません", id.0),
+            },
+            Ok(Input::View(None)) => {
+                for (n, client) in clients.iter().enumerate() {
+                    print!("{}", project(&client.state.borrow(), &PlayerId(n)));
+                }
+            }
+            Ok(Input::Help) => println!("{}", HELP),
+            Ok(Input::Quit) => break,
+            Err(message) => println!("{}", message),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::{fmt::Write, slice};
+
+use domain::{
+    function::{is_finished, ranking},
+    model::{BoardState, HintId, PlayerId, TradeStatus},
+    profile::Profiles,
+    state::AppState,
+};
+
+fn name(profiles: &Profiles, id: &PlayerId) -> String {
+    profiles
+        .find(id)
+        .map_or_else(|| format!("{}", id.0), |profile| format!("{}:{}", id.0, profile.display_name))
+}
+
+fn hints(board: &BoardState, ids: &[HintId]) -> String {
+    ids.iter()
+        .map(|id| {
+            let text = board.hints.get(id).map_or("?", |hint| hint.text.as_str());
+            format!("#{} {}", id.0, text)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// そのプレイヤーの画面に出る情報だけを表示する
+pub fn project(state: &AppState, id: &PlayerId) -> String {
+    let mut out = String::new();
+    match state {
+        AppState::Blank => writeln!(out, "== {} ==\n参加者の登録待ち", id.0).unwrap(),
+        AppState::Lobby(lobby) => {
+            writeln!(out, "== {} ==\n開始待ち", id.0).unwrap();
+            for member in lobby.members.iter() {
+                writeln!(out, "参加者: {}", member.name).unwrap();
+            }
+        }
+        AppState::StandbyPassword(profiles, inputs, setting) => {
+            writeln!(out, "== {} ==", name(profiles, id)).unwrap();
+            let pushed = inputs.iter().any(|input| &input.id == id);
+            writeln!(
+                out,
+                "あいことばとヒント{}個の入力{}",
+                setting.hints_num,
+                if pushed { "済み。ほかの人を待っています" } else { "待ち" }
+            )
+            .unwrap();
+        }
+        AppState::Board(board, profiles) => {
+            writeln!(out, "== {} ==", name(profiles, id)).unwrap();
+            let player = match board.players.get(id) {
+                Some(player) => player,
+                None => {
+                    writeln!(out, "参加していません").unwrap();
+                    return out;
+                }
+            };
+            if let Some(role) = &player.role {
+                writeln!(out, "役職: {:?}{}", role, if player.ability_used { " (使用済み)" } else { "" }).unwrap();
+            }
+            writeln!(out, "あいことば: {}", player.password).unwrap();
+            writeln!(out, "自分のヒント: {}", hints(board, &player.hints)).unwrap();
+            writeln!(out, "ターゲットのヒント: {}", hints(board, slice::from_ref(&player.knowledges.target))).unwrap();
+            writeln!(out, "配られたヒント: {}", hints(board, &player.knowledges.others)).unwrap();
+            if !player.knowledges.revealed.is_empty() {
+                writeln!(out, "調べたヒント: {}", hints(board, &player.knowledges.revealed)).unwrap();
+            }
+            let left = player.guesses_left.map_or("無制限".to_string(), |left| left.to_string());
+            writeln!(out, "推理: 残り{} 得点{} クールダウン{}", left, player.score, player.cooldown).unwrap();
+            if player.found {
+                writeln!(out, "ターゲットを見つけました").unwrap();
+            }
+            if player.eliminated {
+                writeln!(out, "脱落しました").unwrap();
+            }
+            for (index, trade) in board.trades.iter().enumerate() {
+                if &trade.from != id && &trade.to != id {
+                    continue;
+                }
+                let status = match trade.status {
+                    TradeStatus::Pending => "保留中",
+                    TradeStatus::Accepted => "成立",
+                    TradeStatus::Declined => "拒否",
+                    TradeStatus::Cancelled => "取り消し",
+                };
+                writeln!(
+                    out,
+                    "交換{}: {} の {} と {} の {} ({})",
+                    index,
+                    name(profiles, &trade.from),
+                    hints(board, slice::from_ref(&trade.offer)),
+                    name(profiles, &trade.to),
+                    hints(board, slice::from_ref(&trade.request)),
+                    status
+                )
+                .unwrap();
+            }
+            if is_finished(board) {
+                writeln!(out, "ゲーム終了").unwrap();
+                for (index, standing) in ranking(board).iter().enumerate() {
+                    writeln!(
+                        out,
+                        "{}. {} {}pt{}",
+                        index + 1,
+                        name(profiles, &standing.player),
+                        standing.score,
+                        standing.password.as_ref().map_or(String::new(), |password| format!(" あいことば: {}", password))
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+    out
+}
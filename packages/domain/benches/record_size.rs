@@ -0,0 +1,53 @@
+//! 30人の対局で、レコードの保存形式ごとの大きさを比べる
+//! cargo bench -p domain --bench record_size --features fixture
+
+use domain::{
+    encoding::encode_compact,
+    fixture::{authored, setup_with},
+    function::InitPlayer,
+    migration::StoredRecord,
+    model::PlayerId,
+    state::{AppCommand, AppCore, AppState},
+};
+use exprocess::core::ExprocessCore;
+
+const PLAYERS: usize = 30;
+
+fn play() -> Vec<StoredRecord> {
+    let mut commands = setup_with(PLAYERS, |n| InitPlayer {
+        id: PlayerId(n),
+        password: format!("password-{}", n),
+        hints: (0..3).map(|m| format!("プレイヤー{}のヒント{}", n, m)).collect(),
+    });
+    commands.push(authored(0, AppCommand::TakeSnapshot));
+    let mut state = AppState::Blank;
+    commands
+        .into_iter()
+        .map(|command| {
+            let result = AppCore::resolve(&state, command.clone());
+            AppCore::reducer(&mut state, result.clone());
+            StoredRecord::new(&command, &result).unwrap()
+        })
+        .collect()
+}
+
+/// Firestoreに書き込まれるcommandとresultのJSON文字列の長さ
+fn json_len(record: &StoredRecord) -> usize {
+    serde_json::to_string(&record.command.command).unwrap().len() + serde_json::to_string(&record.result).unwrap().len()
+}
+
+fn main() {
+    let records = play();
+    let init_board = &records[PLAYERS];
+    let snapshot = &records[PLAYERS + 1];
+    println!("{:<12}{:>10}{:>10}{:>8}", "record", "json", "compact", "ratio");
+    for (name, records) in [
+        ("InitBoard", std::slice::from_ref(init_board)),
+        ("Snapshot", std::slice::from_ref(snapshot)),
+        ("all", records.as_slice()),
+    ] {
+        let json: usize = records.iter().map(json_len).sum();
+        let compact: usize = records.iter().map(|record| encode_compact(record).unwrap().len()).sum();
+        println!("{:<12}{:>10}{:>10}{:>8.2}", name, json, compact, compact as f64 / json as f64);
+    }
+}
@@ -0,0 +1,53 @@
+use crate::state::AppState;
+
+/// FNV-1a
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// 状態のハッシュ。同じレコードを畳み込んだクライアントどうしなら一致する。
+/// 型の並びに左右されないように、キーを並べ替えたJSONから求める
+pub fn checksum(state: &AppState) -> String {
+    // serde_json::Valueのオブジェクトはキーの順に並ぶ
+    let canonical = serde_json::to_value(state).expect("state is always serializable");
+    format!("{:016x}", fnv1a(canonical.to_string().as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        fixture::{replay, setup},
+        model::PlayerId,
+        state::AppState,
+    };
+
+    use super::checksum;
+
+    fn play(players: usize) -> AppState {
+        replay(setup(players))
+    }
+
+    #[test]
+    fn test_same_records_same_checksum() {
+        let state = play(12);
+        assert!(matches!(state, AppState::Board(_, _)));
+        for _ in 0..10 {
+            let other = play(12);
+            assert_eq!(serde_json::to_string(&other).unwrap(), serde_json::to_string(&state).unwrap());
+            assert_eq!(checksum(&other), checksum(&state));
+        }
+        let round_trip: AppState = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert_eq!(checksum(&round_trip), checksum(&state));
+    }
+
+    #[test]
+    fn test_different_state_different_checksum() {
+        let mut state = play(3);
+        let before = checksum(&state);
+        if let AppState::Board(board, _) = &mut state {
+            board.players.get_mut(&PlayerId(1)).unwrap().score += 1;
+        }
+        assert_ne!(checksum(&state), before);
+        assert_ne!(checksum(&AppState::Blank), before);
+    }
+}
@@ -0,0 +1,104 @@
+use std::{fmt::{self, Display, Formatter}, io::Read};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Serialize, Deserialize};
+
+use crate::migration::{MigrationError, StoredRecord};
+
+/// 部屋ごとに選ぶ、レコードの保存形式
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordEncoding {
+    /// commandとresultをそれぞれJSONの文字列で持つ
+    #[default]
+    Json,
+    /// レコードをまとめてCBORにして圧縮し、base64にしたもの
+    Compact
+}
+
+#[derive(Debug)]
+pub enum EncodingError {
+    Base64(base64::DecodeError),
+    Deflate(std::io::Error),
+    Cbor(String)
+}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::Base64(err) => write!(f, "invalid base64: {}", err),
+            EncodingError::Deflate(err) => write!(f, "invalid deflate stream: {}", err),
+            EncodingError::Cbor(err) => write!(f, "invalid cbor: {}", err),
+        }
+    }
+}
+
+pub fn encode_compact(record: &StoredRecord) -> Result<String, EncodingError> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    ciborium::ser::into_writer(record, &mut encoder).map_err(|err| EncodingError::Cbor(err.to_string()))?;
+    let bytes = encoder.finish().map_err(EncodingError::Deflate)?;
+    Ok(base64::encode(bytes))
+}
+
+pub fn decode_compact(payload: &str) -> Result<StoredRecord, EncodingError> {
+    let bytes = base64::decode(payload).map_err(EncodingError::Base64)?;
+    let mut cbor = Vec::new();
+    DeflateDecoder::new(bytes.as_slice()).read_to_end(&mut cbor).map_err(EncodingError::Deflate)?;
+    ciborium::de::from_reader(cbor.as_slice()).map_err(|err| EncodingError::Cbor(err.to_string()))
+}
+
+/// どちらの形式で保存されたレコードも受け取れる
+#[derive(Debug,Clone,PartialEq,Deserialize)]
+#[serde(untagged)]
+pub enum EncodedRecord {
+    Compact { payload: String },
+    Json(StoredRecord)
+}
+
+impl EncodedRecord {
+    pub fn decode(self) -> Result<StoredRecord, MigrationError> {
+        match self {
+            EncodedRecord::Compact { payload } => decode_compact(payload.as_str()).map_err(MigrationError::Encoding),
+            EncodedRecord::Json(record) => Ok(record),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        migration::StoredRecord,
+        state::{AppCommand, AppResult, AuthoredCommand},
+    };
+
+    use super::{decode_compact, encode_compact, EncodedRecord, EncodingError};
+
+    fn records() -> Vec<StoredRecord> {
+        let records: Vec<StoredRecord> = serde_json::from_str(include_str!("../golden/v3.json")).unwrap();
+        records
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        for record in records() {
+            let payload = encode_compact(&record).unwrap();
+            assert_eq!(decode_compact(payload.as_str()).unwrap(), record);
+            let encoded: EncodedRecord = serde_json::from_value(serde_json::json!({ "payload": payload })).unwrap();
+            assert_eq!(encoded.decode().unwrap(), record);
+        }
+    }
+
+    #[test]
+    fn test_json_is_still_readable() {
+        let command = AuthoredCommand::new("member-0".to_string(), AppCommand::TakeSnapshot);
+        let record = StoredRecord::new(&command, &AppResult::Rejected(crate::model::InvalidCommand::Phase)).unwrap();
+        let encoded: EncodedRecord = serde_json::from_value(serde_json::to_value(&record).unwrap()).unwrap();
+        assert_eq!(encoded, EncodedRecord::Json(record));
+    }
+
+    #[test]
+    fn test_reject_broken_payload() {
+        assert!(matches!(decode_compact("not base64!"), Err(EncodingError::Base64(_))));
+        assert!(matches!(decode_compact("AAAA"), Err(EncodingError::Deflate(_) | EncodingError::Cbor(_))));
+    }
+}
@@ -0,0 +1,54 @@
+//! テストとベンチで使う、参加者を決めて全員があいことばを入力するまでの対局
+
+use exprocess::core::ExprocessCore;
+
+use crate::{
+    function::InitPlayer,
+    model::PlayerId,
+    profile::Profiles,
+    state::{AppCommand, AppCore, AppState, AuthoredCommand},
+};
+
+pub fn member_id(n: usize) -> String {
+    format!("member-{}", n)
+}
+
+/// member-0から順に参加した参加者。ホストはmember-0
+pub fn profiles(players: usize) -> Profiles {
+    Profiles::from_roster((0..players).map(|n| (member_id(n), n.to_string())), member_id(0))
+}
+
+pub fn input(n: usize) -> InitPlayer {
+    InitPlayer {
+        id: PlayerId(n),
+        password: n.to_string(),
+        hints: (0..3).map(|m| format!("{}-{}", n, m)).collect(),
+    }
+}
+
+/// n番目に参加したメンバーが送ったコマンド
+pub fn authored(n: usize, command: AppCommand) -> AuthoredCommand {
+    AuthoredCommand::new(member_id(n), command)
+}
+
+/// 参加者を決めて、全員があいことばを入力するまでのコマンド
+pub fn setup(players: usize) -> Vec<AuthoredCommand> {
+    setup_with(players, input)
+}
+
+/// あいことばとヒントを変えたいとき
+pub fn setup_with<F: Fn(usize) -> InitPlayer>(players: usize, input: F) -> Vec<AuthoredCommand> {
+    std::iter::once(authored(0, AppCommand::InitProfile(profiles(players))))
+        .chain((0..players).map(|n| authored(n, AppCommand::PushPassword(input(n)))))
+        .collect()
+}
+
+/// 最初から順にresolveして反映した状態
+pub fn replay<I: IntoIterator<Item = AuthoredCommand>>(commands: I) -> AppState {
+    let mut state = AppCore::init();
+    for command in commands {
+        let result = AppCore::resolve(&state, command);
+        AppCore::reducer(&mut state, result);
+    }
+    state
+}
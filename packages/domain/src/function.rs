@@ -1,9 +1,9 @@
-use std::{collections::HashMap, hash::Hash, iter::repeat};
+use std::{collections::{BTreeMap, HashMap}, hash::Hash, iter::repeat};
 
 use rand::{prelude::SliceRandom, Rng};
 use serde::{Serialize, Deserialize};
 
-use crate::model::{Hint, HintId, Player, PlayerId};
+use crate::model::{Ability, AbilityEffect, Guess, GuessOutcome, GuessRule, Hint, HintId, InvalidCommand, Penalty, Player, PlayerId, Role, Trade, TradeEvent, TradeId, TradeStatus};
 
 use super::model::{BoardState, PlayerKnowledges};
 
@@ -11,9 +11,11 @@ use super::model::{BoardState, PlayerKnowledges};
 pub struct InitBoard {
     pub players: Vec<InitPlayer>,
     pub hints_num: usize,
+    pub roles: Vec<Role>,
+    pub guess_rule: GuessRule,
 }
 
-#[derive(Serialize,Deserialize,Clone)]
+#[derive(Serialize,Deserialize,Clone,Debug,PartialEq)]
 pub struct InitPlayer {
     pub id: PlayerId,
     pub password: String,
@@ -37,8 +39,11 @@ pub fn init<R: Rng + Clone>(init: InitBoard, rng: &mut R) -> BoardState {
 
     let mut player_2_target = shuffle_shift(players_base.iter().map(|(p,_)| (p.clone(),p.clone())).collect(), rng);
     let (hints, mut players_hints) = extract_dictionary(players_hints, HintId);
-    let mut knowledges = hand_out_hints(players_hints.iter(), init.hints_num, &player_2_target, rng);
-    BoardState {
+    // HashMapの順番に依存しないように、入力の順に配る
+    let players_hints_ordered = players_id.iter().map(|id| (id, players_hints.get(id).expect("TODO")));
+    let mut knowledges = hand_out_hints(players_hints_ordered, init.hints_num, &player_2_target, rng);
+    let mut roles = assign_roles(&players_id, init.roles, rng);
+    let mut board = BoardState {
         hints,
         players: players_base.into_iter().map(|(id,password)| {
             (
@@ -48,17 +53,302 @@ pub fn init<R: Rng + Clone>(init: InitBoard, rng: &mut R) -> BoardState {
                     hints: players_hints.remove(&id).expect("TODO"),
                     target: player_2_target.remove(&id).expect("TODO"),
                     knowledges: knowledges.remove(&id).expect("TODO"),
+                    role: roles.remove(&id),
+                    ability_used: false,
+                    guarded: false,
+                    guesses_left: init.guess_rule.limit,
+                    score: 0,
+                    cooldown: 0,
+                    found: false,
+                    eliminated: false,
                 },
             )
         }).collect(),
+        guess_rule: init.guess_rule,
+        guesses: Vec::new(),
+        trades: Vec::new(),
+        started_at: None,
+    };
+    fake_decoy_hints(&mut board, &players_id, rng);
+    board
+}
+
+fn find_player<'a>(board: &'a BoardState, id: &PlayerId) -> Result<&'a Player, InvalidCommand> {
+    board.players.get(id).ok_or(InvalidCommand::UnknownPlayer)
+}
+
+pub fn guess(board: &BoardState, guesser: PlayerId, suspect: PlayerId) -> Result<Guess, InvalidCommand> {
+    let player = find_player(board, &guesser)?;
+    let suspected = find_player(board, &suspect)?;
+    if player.found || player.eliminated || player.cooldown > 0 || player.guesses_left == Some(0) || guesser == suspect {
+        return Err(InvalidCommand::NotAllowed);
+    }
+    // 守られている人への推理は、当たっていても防がれる
+    let outcome = if suspected.guarded {
+        GuessOutcome::Blocked
+    } else if player.target == suspect {
+        GuessOutcome::Found
+    } else {
+        GuessOutcome::Wrong
+    };
+    Ok(Guess { guesser, suspect, outcome, at: None })
+}
+
+pub fn apply_guess(board: &mut BoardState, guess: Guess) {
+    // 盤面にいないプレイヤーの推理は書き換えられた結果なので、何も変えない
+    if !board.players.contains_key(&guess.guesser) || !board.players.contains_key(&guess.suspect) {
+        return;
+    }
+    for (id, player) in board.players.iter_mut() {
+        if id != &guess.guesser {
+            player.cooldown = player.cooldown.saturating_sub(1);
+        }
+    }
+    let rule = &board.guess_rule;
+    if let Some(player) = board.players.get_mut(&guess.guesser) {
+        player.guesses_left = player.guesses_left.map(|left| left.saturating_sub(1));
+        match guess.outcome {
+            GuessOutcome::Found => {
+                player.found = true;
+                player.score += 1;
+            },
+            GuessOutcome::Wrong => match rule.penalty {
+                Penalty::None => {},
+                Penalty::Score(score) => player.score -= score,
+                Penalty::Cooldown(cooldown) => player.cooldown = cooldown,
+            },
+            GuessOutcome::Blocked => {},
+        }
+        if rule.elimination && !player.found && player.guesses_left == Some(0) {
+            player.eliminated = true;
+        }
+    }
+    if let (GuessOutcome::Blocked, Some(suspect)) = (&guess.outcome, board.players.get_mut(&guess.suspect)) {
+        suspect.guarded = false;
+    }
+    board.guesses.push(guess);
+    // 残っている全員がクールダウン中なら、誰も推理できない番は飛ばす
+    let skipped = board.players.values().filter(|player| is_hunting(player)).map(|player| player.cooldown).min().unwrap_or(0);
+    for player in board.players.values_mut().filter(|player| is_hunting(player)) {
+        player.cooldown -= skipped;
+    }
+}
+
+/// まだターゲットを探していて、いつか推理できる
+fn is_hunting(player: &Player) -> bool {
+    !(player.found || player.eliminated || player.guesses_left == Some(0))
+}
+
+/// 盤面ができてから最後の推理までの秒数。時刻の無い古いレコードではNone
+pub fn play_time_secs(board: &BoardState) -> Option<u64> {
+    let started_at = board.started_at?;
+    let finished_at = board.guesses.last()?.at?;
+    Some(finished_at.saturating_sub(started_at) / 1000)
+}
+
+/// 全員がターゲットを見つけたか、これ以上推理できなくなったらゲーム終了
+pub fn is_finished(board: &BoardState) -> bool {
+    !board.players.values().any(is_hunting)
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct RevealArrow {
+    pub hunter: PlayerId,
+    pub target: PlayerId,
+    /// hunterにターゲットのヒントとして配られたもの
+    pub hint: HintId,
+    /// 何回目の推理で見つけたか
+    pub found_at: Option<usize>,
+}
+
+/// 誰が誰を探していたかを、ターゲットの連鎖の順に並べる
+pub fn reveal(board: &BoardState) -> Vec<RevealArrow> {
+    let mut arrows: Vec<RevealArrow> = Vec::with_capacity(board.players.len());
+    for start in board.players.keys() {
+        let mut hunter = start;
+        while !arrows.iter().any(|arrow| &arrow.hunter == hunter) {
+            let player = board.players.get(hunter).expect("TODO");
+            let found_at = board.guesses
+                .iter()
+                .position(|guess| &guess.guesser == hunter && guess.outcome == GuessOutcome::Found)
+                .map(|index| index + 1);
+            arrows.push(RevealArrow {
+                hunter: hunter.clone(),
+                target: player.target.clone(),
+                hint: player.knowledges.target.clone(),
+                found_at,
+            });
+            hunter = &player.target;
+        }
+    }
+    arrows
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Standing {
+    pub player: PlayerId,
+    pub score: i32,
+    pub found_at: Option<usize>,
+    /// 誰かに見つけられていれば公開される
+    pub password: Option<String>,
+}
+
+/// スコアが高い順、同点なら早く見つけた順
+pub fn ranking(board: &BoardState) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = reveal(board)
+        .into_iter()
+        .map(|arrow| {
+            let player = board.players.get(&arrow.hunter).expect("TODO");
+            let found = board.players.values().any(|hunter| hunter.target == arrow.hunter && hunter.found);
+            Standing {
+                player: arrow.hunter,
+                score: player.score,
+                found_at: arrow.found_at,
+                password: if found { Some(player.password.clone()) } else { None },
+            }
+        })
+        .collect();
+    standings.sort_by_key(|standing| (-standing.score, standing.found_at.unwrap_or(usize::MAX), standing.player.0));
+    standings
+}
+
+fn holds(board: &BoardState, player: &PlayerId, hint: &HintId) -> bool {
+    board.players.get(player).is_some_and(|player| !player.eliminated && player.knowledges.others.contains(hint))
+}
+
+pub fn propose_trade(board: &BoardState, from: PlayerId, to: PlayerId, offer: HintId, request: HintId) -> Result<TradeEvent, InvalidCommand> {
+    find_player(board, &from)?;
+    find_player(board, &to)?;
+    if from == to || !holds(board, &from, &offer) || !holds(board, &to, &request) {
+        return Err(InvalidCommand::NotAllowed);
+    }
+    Ok(TradeEvent::Proposed(Trade { from, to, offer, request, status: TradeStatus::Pending }))
+}
+
+pub fn answer_trade(board: &BoardState, player: &PlayerId, trade_id: TradeId, accept: bool) -> Result<TradeEvent, InvalidCommand> {
+    let trade = board.trades.get(trade_id.0).ok_or(InvalidCommand::UnknownTrade)?;
+    if &trade.to != player || trade.status != TradeStatus::Pending {
+        return Err(InvalidCommand::NotAllowed);
+    }
+    Ok(if !accept {
+        TradeEvent::Declined(trade_id)
+    } else if holds(board, &trade.from, &trade.offer) && holds(board, &trade.to, &trade.request) {
+        TradeEvent::Accepted(trade_id)
+    } else {
+        TradeEvent::Cancelled(trade_id)
+    })
+}
+
+pub fn apply_trade(board: &mut BoardState, event: TradeEvent) {
+    let (trade_id, status) = match event {
+        TradeEvent::Proposed(trade) => {
+            board.trades.push(trade);
+            return;
+        },
+        TradeEvent::Accepted(trade_id) => (trade_id, TradeStatus::Accepted),
+        TradeEvent::Declined(trade_id) => (trade_id, TradeStatus::Declined),
+        TradeEvent::Cancelled(trade_id) => (trade_id, TradeStatus::Cancelled),
+    };
+    // 知らない取引への返事は書き換えられた結果なので、何も変えない
+    let trade = match board.trades.get_mut(trade_id.0) {
+        Some(trade) => trade,
+        None => return,
+    };
+    trade.status = status;
+    if trade.status == TradeStatus::Accepted {
+        let trade = trade.clone();
+        if let Some(from) = board.players.get_mut(&trade.from) {
+            replace_hint(&mut from.knowledges.others, &trade.offer, trade.request.clone());
+        }
+        if let Some(to) = board.players.get_mut(&trade.to) {
+            replace_hint(&mut to.knowledges.others, &trade.request, trade.offer.clone());
+        }
+    }
+}
+
+fn assign_roles<R: Rng>(players: &[PlayerId], roles: Vec<Role>, rng: &mut R) -> HashMap<PlayerId, Role> {
+    let mut players = players.to_vec();
+    players.shuffle(rng);
+    players.into_iter().zip(roles).collect()
+}
+
+/// Decoyのヒントのうち、ターゲットのヒントとして配られなかったものを1つ、他のプレイヤーのヒントと入れ替える
+fn fake_decoy_hints<R: Rng>(board: &mut BoardState, players: &[PlayerId], rng: &mut R) {
+    let dealt_as_target: Vec<HintId> = board.players.values().map(|p| p.knowledges.target.clone()).collect();
+    let swappable = |player: &Player| -> Vec<HintId> {
+        player.hints.iter().filter(|hint| !dealt_as_target.contains(hint)).cloned().collect()
+    };
+    let originals: Vec<HintId> = players
+        .iter()
+        .filter(|id| board.players.get(id).expect("player in board").role != Some(Role::Decoy))
+        .flat_map(|id| swappable(board.players.get(id).expect("player in board")))
+        .collect();
+    for decoy in players.iter() {
+        let player = board.players.get(decoy).expect("player in board");
+        if player.role != Some(Role::Decoy) {
+            continue;
+        }
+        let replaced = match swappable(player).choose(rng) {
+            Some(hint) => hint.clone(),
+            None => continue,
+        };
+        let original = match originals.choose(rng) {
+            Some(hint) => hint.clone(),
+            None => continue,
+        };
+        // 他のプレイヤーの手札には触れず、Decoyのヒントの中身だけを他人のものの写しにする
+        let copied = board.hints.get(&original).expect("hint in dictionary").clone();
+        board.hints.insert(replaced, copied);
+    }
+}
+
+fn replace_hint(hints: &mut [HintId], from: &HintId, to: HintId) {
+    if let Some(hint) = hints.iter_mut().find(|hint| *hint == from) {
+        *hint = to;
     }
 }
 
-fn extract_dictionary<K: Eq + Hash, Item, Id: Clone + Eq + Hash, F: Fn(usize) -> Id>(
+pub fn use_ability<R: Rng>(board: &BoardState, player_id: &PlayerId, ability: Ability, rng: &mut R) -> Result<AbilityEffect, InvalidCommand> {
+    let player = find_player(board, player_id)?;
+    if player.ability_used || player.eliminated {
+        return Err(InvalidCommand::NotAllowed);
+    }
+    match (&player.role, ability) {
+        (Some(Role::Detective), Ability::Investigate) => {
+            let target = find_player(board, &player.target)?;
+            let known: Vec<&HintId> = [&player.knowledges.target].into_iter().chain(player.knowledges.revealed.iter()).collect();
+            let unknown: Vec<&HintId> = target.hints.iter().filter(|hint| !known.contains(hint)).collect();
+            Ok(AbilityEffect::Investigate(unknown.choose(rng).map(|hint| (*hint).clone())))
+        },
+        (Some(Role::Guardian), Ability::Guard(protected)) => {
+            find_player(board, &protected)?;
+            Ok(AbilityEffect::Guard(protected))
+        },
+        _ => Err(InvalidCommand::NotAllowed),
+    }
+}
+
+pub fn apply_ability(board: &mut BoardState, player_id: &PlayerId, effect: AbilityEffect) {
+    let player = match board.players.get_mut(player_id) {
+        Some(player) => player,
+        None => return,
+    };
+    player.ability_used = true;
+    match effect {
+        AbilityEffect::Investigate(hint) => player.knowledges.revealed.extend(hint),
+        AbilityEffect::Guard(protected) => {
+            if let Some(protected) = board.players.get_mut(&protected) {
+                protected.guarded = true;
+            }
+        },
+    }
+}
+
+fn extract_dictionary<K: Eq + Hash, Item, Id: Clone + Ord, F: Fn(usize) -> Id>(
     inputs: Vec<(K, Vec<Item>)>,
     create_id: F,
-) -> (HashMap<Id, Item>, HashMap<K, Vec<Id>>) {
-    let mut dictionary = HashMap::with_capacity(inputs.iter().flat_map(|(_, vec)| vec).count());
+) -> (BTreeMap<Id, Item>, HashMap<K, Vec<Id>>) {
+    let mut dictionary = BTreeMap::new();
     let mut lists = HashMap::with_capacity(inputs.len());
     for (key, items) in inputs.into_iter() {
         let mut ids = Vec::with_capacity(items.len());
@@ -110,6 +400,7 @@ fn hand_out_hints<'a,R: Rng + Clone,Iter: Iterator<Item=(&'a PlayerId, &'a Vec<H
                 PlayerKnowledges {
                     target: target_hint.clone().clone(),
                     others,
+                    revealed: Vec::new(),
                 },
             )
         })
@@ -204,9 +495,9 @@ mod test {
 
     use std::collections::{HashSet, HashMap};
 
-    use crate::model::{PlayerId, HintId};
+    use crate::model::{PlayerId, HintId, Role, Ability, AbilityEffect, GuessRule, Penalty, Guess, GuessOutcome, TradeEvent, TradeId, TradeStatus};
 
-    use super::{init, InitBoard, InitPlayer, hand_out_hints};
+    use super::{init, InitBoard, InitPlayer, hand_out_hints, use_ability, apply_ability, guess, apply_guess, propose_trade, answer_trade, apply_trade, is_finished, play_time_secs, reveal, ranking};
     use mytil::validate_no_duplicate;
     use rand::{thread_rng,Rng};
 
@@ -232,7 +523,9 @@ mod test {
                             hints: vec!["G".to_owned(),"H".to_owned(),"I".to_owned()]
                         },
                     ], 
-                    hints_num: 3 
+                    hints_num: 3,
+                    roles: vec![],
+                    guess_rule: GuessRule { limit: None, penalty: Penalty::None, elimination: false }
                 },
                 rng
             );
@@ -307,4 +600,239 @@ mod test {
     }
 
     
+    fn init_board_with_rule(roles: Vec<Role>, guess_rule: GuessRule) -> InitBoard {
+        InitBoard {
+            players: (0..4).map(|n| InitPlayer {
+                id: PlayerId(n),
+                password: n.to_string(),
+                hints: (0..3).map(|m| format!("{}-{}",n,m)).collect()
+            }).collect(),
+            hints_num: 3,
+            roles,
+            guess_rule
+        }
+    }
+
+    fn init_board(roles: Vec<Role>) -> InitBoard {
+        init_board_with_rule(roles, GuessRule { limit: None, penalty: Penalty::None, elimination: false })
+    }
+
+    #[test]
+    fn test_init_roles() {
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let state = init(init_board(vec![Role::Detective,Role::Decoy,Role::Guardian]),&mut rng);
+            // 役職は指定された数だけ配られているか
+            let mut roles: Vec<_> = state.players.values().filter_map(|p| p.role.clone()).collect();
+            roles.sort_by_key(|role| format!("{:?}",role));
+            assert_eq!(roles,vec![Role::Decoy,Role::Detective,Role::Guardian]);
+            // Decoyがいてもヒントは重複なく誰かのものになっているか
+            assert!(validate_no_duplicate(state.players.values().flat_map(|p| p.hints.iter())));
+            assert_eq!(state.players.values().flat_map(|p| p.hints.iter()).count(),12);
+            // 偽物はDecoyの手札の中身だけで、他のプレイヤーのヒントは書き換えない
+            assert_eq!(state.hints.len(),12);
+            let decoy = state.players.values().find(|p| p.role == Some(Role::Decoy)).unwrap();
+            let texts: Vec<&String> = state.players.values().filter(|p| p.role != Some(Role::Decoy)).flat_map(|p| p.hints.iter()).map(|hint| &state.hints.get(hint).unwrap().text).collect();
+            assert!(decoy.hints.iter().any(|hint| texts.contains(&&state.hints.get(hint).unwrap().text)));
+            for player in state.players.values().filter(|p| p.role != Some(Role::Decoy)) {
+                assert!(player.hints.iter().all(|hint| state.hints.get(hint).unwrap().text.starts_with(&format!("{}-",player.password))));
+            }
+            // ターゲットのヒントは本物のまま
+            for player in state.players.values() {
+                assert!(state.players.get(&player.target).unwrap().hints.contains(&player.knowledges.target));
+            }
+        }
+    }
+
+    #[test]
+    fn test_use_ability() {
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let mut state = init(init_board(vec![Role::Detective,Role::Guardian]),&mut rng);
+            let find = |role: Role| state.players.iter().find(|(_,p)| p.role == Some(role.clone())).map(|(id,_)| id.clone()).unwrap();
+            let detective = find(Role::Detective);
+            let guardian = find(Role::Guardian);
+
+            let effect = use_ability(&state,&detective,Ability::Investigate,&mut rng).unwrap();
+            apply_ability(&mut state,&detective,effect);
+            let player = state.players.get(&detective).unwrap();
+            let target = state.players.get(&player.target).unwrap();
+            assert!(player.ability_used);
+            assert_eq!(player.knowledges.revealed.len(),1);
+            assert!(player.knowledges.revealed.iter().all(|hint| target.hints.contains(hint) && hint != &player.knowledges.target));
+
+            let effect = use_ability(&state,&guardian,Ability::Guard(PlayerId(0)),&mut rng).unwrap();
+            assert!(matches!(effect,AbilityEffect::Guard(PlayerId(0))));
+            apply_ability(&mut state,&guardian,effect);
+            assert!(state.players.get(&PlayerId(0)).unwrap().guarded);
+        }
+    }
+
+    #[test]
+    fn test_guess() {
+        let mut rng = thread_rng();
+        let mut state = init(init_board_with_rule(vec![], GuessRule { limit: Some(2), penalty: Penalty::Score(3), elimination: true }),&mut rng);
+        let hunter = PlayerId(0);
+        let target = state.players.get(&hunter).unwrap().target.clone();
+        let wrong: Vec<_> = (1..4).map(PlayerId).filter(|id| id != &target).collect();
+
+        let result = guess(&state, hunter.clone(), wrong[0].clone()).unwrap();
+        assert_eq!(result.outcome, GuessOutcome::Wrong);
+        apply_guess(&mut state, result);
+        let player = state.players.get(&hunter).unwrap();
+        assert_eq!((player.score, player.guesses_left, player.eliminated), (-3, Some(1), false));
+
+        let result = guess(&state, hunter.clone(), wrong[1].clone()).unwrap();
+        apply_guess(&mut state, result);
+        let player = state.players.get(&hunter).unwrap();
+        // 使い切ったら脱落するが、他のプレイヤーからは見つけられる
+        assert_eq!((player.score, player.guesses_left, player.eliminated), (-6, Some(0), true));
+        let hunter_of_hunter = state.players.iter().find(|(_, p)| p.target == hunter).map(|(id, _)| id.clone()).unwrap();
+        let result = guess(&state, hunter_of_hunter.clone(), hunter).unwrap();
+        assert_eq!(result.outcome, GuessOutcome::Found);
+        apply_guess(&mut state, result);
+        assert!(state.players.get(&hunter_of_hunter).unwrap().found);
+        assert_eq!(state.guesses.len(), 3);
+    }
+
+    #[test]
+    fn test_guess_cooldown_and_guard() {
+        let mut rng = thread_rng();
+        let mut state = init(init_board_with_rule(vec![], GuessRule { limit: None, penalty: Penalty::Cooldown(1), elimination: false }),&mut rng);
+        let hunter = PlayerId(0);
+        let target = state.players.get(&hunter).unwrap().target.clone();
+        let wrong: Vec<_> = (1..4).map(PlayerId).filter(|id| id != &target).collect();
+        state.players.get_mut(&wrong[0]).unwrap().guarded = true;
+
+        let result = guess(&state, hunter.clone(), wrong[0].clone()).unwrap();
+        assert_eq!(result.outcome, GuessOutcome::Blocked);
+        apply_guess(&mut state, result);
+        assert!(!state.players.get(&wrong[0]).unwrap().guarded);
+        assert_eq!(state.players.get(&hunter).unwrap().cooldown, 0);
+
+        // ターゲットでも守られていれば当たらない
+        state.players.get_mut(&target).unwrap().guarded = true;
+        let result = guess(&state, hunter.clone(), target.clone()).unwrap();
+        assert_eq!(result.outcome, GuessOutcome::Blocked);
+        apply_guess(&mut state, result);
+        assert!(!state.players.get(&hunter).unwrap().found);
+
+        let result = guess(&state, hunter.clone(), wrong[1].clone()).unwrap();
+        apply_guess(&mut state, result);
+        assert_eq!(state.players.get(&hunter).unwrap().cooldown, 1);
+        // 他のプレイヤーが推理するとクールダウンが明ける
+        let other = wrong[1].clone();
+        let other_target = state.players.get(&other).unwrap().target.clone();
+        let result = guess(&state, other, other_target).unwrap();
+        apply_guess(&mut state, result);
+        assert_eq!(state.players.get(&hunter).unwrap().cooldown, 0);
+    }
+
+    #[test]
+    fn test_cooldown_with_one_hunter_left() {
+        let mut rng = thread_rng();
+        let mut state = init(init_board_with_rule(vec![], GuessRule { limit: None, penalty: Penalty::Cooldown(2), elimination: false }),&mut rng);
+        let hunter = PlayerId(0);
+        for (_, player) in state.players.iter_mut().filter(|(id, _)| **id != hunter) {
+            player.found = true;
+        }
+        let target = state.players.get(&hunter).unwrap().target.clone();
+        let wrong = (1..4).map(PlayerId).find(|id| id != &target).unwrap();
+        let result = guess(&state, hunter.clone(), wrong).unwrap();
+        apply_guess(&mut state, result);
+        // クールダウンを明けさせる人がいないので、待たずに次の推理ができる
+        assert_eq!(state.players.get(&hunter).unwrap().cooldown, 0);
+        assert!(!is_finished(&state));
+        let result = guess(&state, hunter.clone(), target).unwrap();
+        assert_eq!(result.outcome, GuessOutcome::Found);
+        apply_guess(&mut state, result);
+        assert!(is_finished(&state));
+    }
+
+    #[test]
+    fn test_play_time() {
+        let mut rng = thread_rng();
+        let mut state = init(init_board(vec![]),&mut rng);
+        assert_eq!(play_time_secs(&state), None);
+        state.started_at = Some(1_000);
+        let hunter = PlayerId(0);
+        let target = state.players.get(&hunter).unwrap().target.clone();
+        let result = guess(&state, hunter, target).unwrap();
+        apply_guess(&mut state, Guess { at: Some(126_500), ..result });
+        assert_eq!(play_time_secs(&state), Some(125));
+    }
+
+    #[test]
+    fn test_trade() {
+        let mut rng = thread_rng();
+        let mut state = init(init_board(vec![]),&mut rng);
+        let (a, b, c) = (PlayerId(0), PlayerId(1), PlayerId(2));
+        let hint_of = |state: &crate::model::BoardState, id: &PlayerId, index: usize| state.players.get(id).unwrap().knowledges.others[index].clone();
+        let (offer, request) = (hint_of(&state, &a, 0), hint_of(&state, &b, 0));
+
+        let event = propose_trade(&state, a.clone(), b.clone(), offer.clone(), request.clone()).unwrap();
+        apply_trade(&mut state, event);
+        let event = answer_trade(&state, &b, TradeId(0), true).unwrap();
+        assert!(matches!(event, TradeEvent::Accepted(TradeId(0))));
+        apply_trade(&mut state, event);
+        assert_eq!(state.trades[0].status, TradeStatus::Accepted);
+        assert!(state.players.get(&a).unwrap().knowledges.others.contains(&request));
+        assert!(state.players.get(&b).unwrap().knowledges.others.contains(&offer));
+        assert!(validate_no_duplicate(state.players.values().flat_map(|p| p.knowledges.others.iter().chain([&p.knowledges.target]))));
+
+        // 承認前に手放したヒントの交換は取り消される
+        let offer = hint_of(&state, &a, 1);
+        let event = propose_trade(&state, a.clone(), c.clone(), offer.clone(), hint_of(&state, &c, 0)).unwrap();
+        apply_trade(&mut state, event);
+        let event = propose_trade(&state, a.clone(), b.clone(), offer, hint_of(&state, &b, 1)).unwrap();
+        apply_trade(&mut state, event);
+        let event = answer_trade(&state, &b, TradeId(2), true).unwrap();
+        apply_trade(&mut state, event);
+        let event = answer_trade(&state, &c, TradeId(1), true).unwrap();
+        assert!(matches!(event, TradeEvent::Cancelled(TradeId(1))));
+        apply_trade(&mut state, event);
+        assert_eq!(state.trades[1].status, TradeStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_ignore_forged_results() {
+        let mut rng = thread_rng();
+        let mut state = init(init_board(vec![]),&mut rng);
+        let before = state.clone();
+        // resolveを通らない、盤面にいないプレイヤーや無い取引を指す結果が届いても壊れない
+        apply_guess(&mut state, Guess { guesser: PlayerId(9), suspect: PlayerId(0), outcome: GuessOutcome::Found, at: None });
+        apply_guess(&mut state, Guess { guesser: PlayerId(0), suspect: PlayerId(9), outcome: GuessOutcome::Blocked, at: None });
+        apply_trade(&mut state, TradeEvent::Accepted(TradeId(7)));
+        apply_ability(&mut state, &PlayerId(9), AbilityEffect::Guard(PlayerId(0)));
+        assert_eq!(state, before);
+        apply_ability(&mut state, &PlayerId(0), AbilityEffect::Guard(PlayerId(9)));
+        assert!(state.players.get(&PlayerId(0)).unwrap().ability_used);
+        assert!(state.players.values().all(|player| !player.guarded));
+    }
+
+    #[test]
+    fn test_reveal() {
+        let mut rng = thread_rng();
+        let mut state = init(init_board(vec![]),&mut rng);
+        let arrows = reveal(&state);
+        assert_eq!(arrows.len(), 4);
+        // 連鎖の順に並んでいるか
+        for (index, arrow) in arrows.iter().enumerate() {
+            assert_eq!(arrow.target, arrows[(index + 1) % arrows.len()].hunter);
+            assert_eq!(arrow.found_at, None);
+        }
+        assert!(!is_finished(&state));
+        for arrow in arrows.iter().rev() {
+            let result = guess(&state, arrow.hunter.clone(), arrow.target.clone()).unwrap();
+            apply_guess(&mut state, result);
+        }
+        assert!(is_finished(&state));
+        let found: Vec<_> = reveal(&state).iter().map(|arrow| arrow.found_at).collect();
+        assert_eq!(found, vec![Some(4), Some(3), Some(2), Some(1)]);
+        // 同点なら早く見つけた順で、全員見つけられているのであいことばも公開される
+        let standings = ranking(&state);
+        let expected: Vec<_> = arrows.iter().rev().map(|arrow| arrow.hunter.clone()).collect();
+        assert_eq!(standings.iter().map(|s| s.player.clone()).collect::<Vec<_>>(), expected);
+        assert!(standings.iter().all(|s| s.password == Some(s.player.0.to_string())));
+    }
 }
@@ -1,4 +1,14 @@
 pub mod function;
 pub mod model;
 pub mod state;
-pub mod profile;
\ No newline at end of file
+pub mod profile;
+pub mod notebook;
+pub mod memory;
+pub mod verify;
+pub mod sequence;
+pub mod snapshot;
+pub mod migration;
+pub mod encoding;
+pub mod checksum;
+#[cfg(any(test, feature = "fixture"))]
+pub mod fixture;
\ No newline at end of file
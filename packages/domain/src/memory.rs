@@ -0,0 +1,199 @@
+use std::{cell::RefCell, rc::Rc};
+
+use exprocess::{client::{Record, RecordSync, Repository}, core::ExprocessCore};
+
+/// 複数のRepositoryで共有するレコードのログ。
+/// ブラウザなしでのテストや、1つのプロセス内で遊ぶときに使う
+pub struct MemoryLog<Core: ExprocessCore> {
+    inner: Rc<RefCell<Inner<Core>>>
+}
+
+impl <Core: ExprocessCore> Clone for MemoryLog<Core> {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone() }
+    }
+}
+
+struct Inner<Core: ExprocessCore> {
+    records: Vec<Rc<Record<Core>>>,
+    subscribers: Vec<Subscriber<Core>>,
+    next_subscriber: usize,
+    delivering: bool
+}
+
+type Listener<Core> = Box<dyn FnMut(Vec<RecordSync<Core>>)>;
+
+struct Subscriber<Core: ExprocessCore> {
+    id: usize,
+    /// 配信済みのレコード数
+    cursor: usize,
+    /// 配信中は取り出されている
+    listener: Option<Listener<Core>>
+}
+
+impl <Core: ExprocessCore> Default for MemoryLog<Core> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <Core: ExprocessCore> MemoryLog<Core> {
+    pub fn new() -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(Inner {
+                records: Vec::new(),
+                subscribers: Vec::new(),
+                next_subscriber: 0,
+                delivering: false
+            }))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.borrow().records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&self, record: Record<Core>) {
+        self.inner.borrow_mut().records.push(Rc::new(record));
+        self.deliver();
+    }
+
+    fn subscribe(&self, listener: Listener<Core>) -> usize {
+        let id = {
+            let mut inner = self.inner.borrow_mut();
+            let id = inner.next_subscriber;
+            inner.next_subscriber += 1;
+            inner.subscribers.push(Subscriber { id, cursor: 0, listener: Some(listener) });
+            id
+        };
+        self.deliver();
+        id
+    }
+
+    fn unsubscribe(&self, id: usize) {
+        self.inner.borrow_mut().subscribers.retain(|subscriber| subscriber.id != id);
+    }
+
+    /// 未配信のレコードを全員に届ける。
+    /// listenerの中でpushされた場合も、同じループの中で順番に届ける
+    fn deliver(&self) {
+        if self.inner.borrow().delivering {
+            return;
+        }
+        self.inner.borrow_mut().delivering = true;
+        loop {
+            let next = {
+                let mut inner = self.inner.borrow_mut();
+                let len = inner.records.len();
+                let pending = inner.subscribers
+                    .iter_mut()
+                    .find(|subscriber| subscriber.cursor < len && subscriber.listener.is_some());
+                match pending {
+                    Some(subscriber) => {
+                        let from = subscriber.cursor;
+                        subscriber.cursor = len;
+                        let listener = subscriber.listener.take().expect("checked above");
+                        Some((subscriber.id, listener, inner.records[from..len].to_vec()))
+                    },
+                    None => None,
+                }
+            };
+            let (id, mut listener, records) = match next {
+                Some(next) => next,
+                None => break,
+            };
+            listener(
+                records
+                    .iter()
+                    .map(|record| RecordSync { id: record.id.as_str(), command: &record.command, result: &record.result })
+                    .collect()
+            );
+            let mut inner = self.inner.borrow_mut();
+            if let Some(subscriber) = inner.subscribers.iter_mut().find(|subscriber| subscriber.id == id) {
+                subscriber.listener = Some(listener);
+            }
+        }
+        self.inner.borrow_mut().delivering = false;
+    }
+}
+
+pub struct MemoryRepository<Core: ExprocessCore> {
+    log: MemoryLog<Core>,
+    subscriber: Option<usize>
+}
+
+impl <Core: ExprocessCore> MemoryRepository<Core> {
+    pub fn new(log: &MemoryLog<Core>) -> Self {
+        Self {
+            log: log.clone(),
+            subscriber: None
+        }
+    }
+}
+
+impl <Core: ExprocessCore + 'static,Err> Repository<Core,Err> for MemoryRepository<Core> {
+    fn push(&mut self, record: Record<Core>, _on_error: Box<dyn FnOnce(Err)>) {
+        self.log.push(record);
+    }
+
+    fn sync(&mut self, listener: Box<dyn FnMut(Vec<RecordSync<Core>>)>, _on_error: Box<dyn FnMut(Err)>) {
+        self.subscriber = Some(self.log.subscribe(listener));
+    }
+
+    fn unsync(&mut self) {
+        if let Some(id) = self.subscriber.take() {
+            self.log.unsubscribe(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, convert::Infallible, rc::Rc};
+
+    use exprocess::client::Runner;
+
+    use crate::{
+        fixture::{authored, input, profiles},
+        model::PlayerId,
+        state::{AppCommand, AppCore, AppState},
+    };
+
+    use super::{MemoryLog, MemoryRepository};
+
+    #[test]
+    fn test_clients_converge() {
+        let log = MemoryLog::<AppCore>::new();
+        let states: Vec<Rc<RefCell<Option<AppState>>>> = (0..4).map(|_| Rc::new(RefCell::new(None))).collect();
+        let mut runners: Vec<Runner<AppCore, Infallible>> = states
+            .iter()
+            .map(|state| {
+                let state = state.clone();
+                Runner::start(
+                    MemoryRepository::new(&log),
+                    Box::new(move |_, app: &AppState| {
+                        state.replace(Some(app.clone()));
+                    }),
+                    Box::new(|_| panic!()),
+                )
+            })
+            .collect();
+        runners[0].dispatch(authored(0, AppCommand::InitProfile(profiles(4))));
+        for (n, runner) in runners.iter_mut().enumerate() {
+            runner.dispatch(authored(n, AppCommand::PushPassword(input(n))));
+        }
+        for (n, runner) in runners.iter_mut().enumerate() {
+            runner.dispatch(authored(n, AppCommand::Guess(PlayerId(n), PlayerId((n + 1) % 4))));
+        }
+        assert_eq!(log.len(), 9);
+        let first = states[0].borrow().clone().unwrap();
+        assert!(matches!(first, AppState::Board(_, _)));
+        for state in states.iter() {
+            assert_eq!(state.borrow().as_ref(), Some(&first));
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Map, Value};
+
+use crate::{encoding::EncodingError, state::{AppCommand, AppResult, AuthoredCommand, RngAlgorithm}};
+
+/// 今のレコードの形式
+/// 1: serdeの既定の形式 {"Variant": value}。送り主が無く、盤面はthread_rngで配っていた
+/// 2: {"type": "Variant", "value": value}
+/// 3: 形は2と同じで、resolveの乱数がChaCha8になった
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// コマンドを記録するときのバージョン。古い乱数でresolveしたものは、その乱数を使っていたバージョンで残す
+pub fn schema_version(command: &AuthoredCommand) -> u32 {
+    match command.rng {
+        RngAlgorithm::Unseeded | RngAlgorithm::StdRng => 2,
+        RngAlgorithm::ChaCha8 => SCHEMA_VERSION,
+    }
+}
+
+/// バージョンが無いのは最初の形式
+fn first_version() -> u32 {
+    1
+}
+
+/// 保存されたままのレコードの中身。読むときに今の形式に上げる
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct StoredRecord {
+    #[serde(default = "first_version")]
+    pub version: u32,
+    pub command: StoredCommand,
+    pub result: Value
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct StoredCommand {
+    /// 最初の形式には無い。誰が送ったかは分からない
+    #[serde(default)]
+    pub author: String,
+    pub command: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<u64>
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// 知らないバージョン。新しいクライアントが書いたもの
+    UnknownVersion(u32),
+    Malformed(serde_json::Error),
+    /// 圧縮された形式を戻せなかった
+    Encoding(EncodingError)
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::UnknownVersion(version) => write!(f, "unknown schema version {}", version),
+            MigrationError::Malformed(err) => write!(f, "malformed record: {}", err),
+            MigrationError::Encoding(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<serde_json::Error> for MigrationError {
+    fn from(err: serde_json::Error) -> Self {
+        MigrationError::Malformed(err)
+    }
+}
+
+impl StoredRecord {
+    /// 今の形式で保存する
+    pub fn new(command: &AuthoredCommand, result: &AppResult) -> serde_json::Result<Self> {
+        Ok(Self {
+            version: schema_version(command),
+            command: StoredCommand {
+                author: command.author.clone(),
+                command: serde_json::to_value(&command.command)?,
+                at: command.at
+            },
+            result: serde_json::to_value(result)?
+        })
+    }
+
+    pub fn upgrade(self) -> Result<(AuthoredCommand, AppResult), MigrationError> {
+        let command: AppCommand = serde_json::from_value(upgrade(self.version, self.command.command)?)?;
+        let result: AppResult = serde_json::from_value(upgrade(self.version, self.result)?)?;
+        let rng = match self.version {
+            1 => RngAlgorithm::Unseeded,
+            2 => RngAlgorithm::StdRng,
+            _ => RngAlgorithm::ChaCha8,
+        };
+        Ok((AuthoredCommand { author: self.command.author, command, at: self.command.at, rng }, result))
+    }
+}
+
+/// AppCommandかAppResultのjsonを、versionから今の形式まで順に上げる
+pub fn upgrade(version: u32, mut value: Value) -> Result<Value, MigrationError> {
+    if version == 0 || version > SCHEMA_VERSION {
+        return Err(MigrationError::UnknownVersion(version))
+    }
+    for from in version..SCHEMA_VERSION {
+        value = match from {
+            1 => tag_variant(fill_first_version(value)),
+            2 => value,
+            _ => unreachable!()
+        };
+    }
+    Ok(value)
+}
+
+/// 最初の形式のInitProfileとInitBoardに、後から足したフィールドの既定値を入れる。
+/// ホストは分からないので空にする。推理のルールは、推理の無かった頃に合わせて制限しない
+fn fill_first_version(mut value: Value) -> Value {
+    fn fill(object: &mut Value, defaults: Value) {
+        if let (Value::Object(object), Value::Object(defaults)) = (object, defaults) {
+            for (key, value) in defaults {
+                object.entry(key).or_insert(value);
+            }
+        }
+    }
+    if let Some(profiles) = value.get_mut("InitProfile") {
+        fill(profiles, json!({ "host": "" }));
+    }
+    if let Some(board) = value.get_mut("InitBoard") {
+        fill(board, json!({
+            "guess_rule": { "limit": null, "penalty": "None", "elimination": false },
+            "guesses": [],
+            "trades": []
+        }));
+        if let Some(Value::Object(players)) = board.get_mut("players") {
+            for player in players.values_mut() {
+                fill(player, json!({
+                    "role": null,
+                    "ability_used": false,
+                    "guarded": false,
+                    "guesses_left": null,
+                    "score": 0,
+                    "cooldown": 0,
+                    "found": false,
+                    "eliminated": false
+                }));
+                if let Some(knowledges) = player.get_mut("knowledges") {
+                    fill(knowledges, json!({ "revealed": [] }));
+                }
+            }
+        }
+    }
+    value
+}
+
+/// {"Variant": value}と"Variant"を、{"type": "Variant", "value": value}にする
+fn tag_variant(value: Value) -> Value {
+    let (name, content) = match value {
+        Value::String(name) => (name, None),
+        Value::Object(object) if object.len() == 1 => {
+            let (name, content) = object.into_iter().next().expect("object has one entry");
+            (name, Some(content))
+        },
+        other => return other
+    };
+    let mut tagged = Map::new();
+    tagged.insert("type".to_string(), Value::String(name));
+    if let Some(content) = content {
+        tagged.insert("value".to_string(), content);
+    }
+    Value::Object(tagged)
+}
+
+#[cfg(test)]
+mod test {
+    use exprocess::core::ExprocessCore;
+
+    use crate::state::{AppCommand, AppCore, AppResult, AppState, AuthoredCommand, RngAlgorithm};
+
+    use super::{MigrationError, StoredRecord, SCHEMA_VERSION};
+
+    /// 過去のバージョンで保存された対局。v1は最初の形式のクライアントが書いたもの
+    const GOLDEN: [(u32, &str); 3] = [
+        (1, include_str!("../golden/v1.json")),
+        (2, include_str!("../golden/v2.json")),
+        (3, include_str!("../golden/v3.json")),
+    ];
+
+    fn load(json: &str) -> Vec<(AuthoredCommand, AppResult)> {
+        let records: Vec<StoredRecord> = serde_json::from_str(json).unwrap();
+        records.into_iter().map(|record| record.upgrade().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_golden_versions() {
+        for (version, json) in GOLDEN {
+            let records: Vec<StoredRecord> = serde_json::from_str(json).unwrap();
+            assert!(records.iter().all(|record| record.version == version));
+        }
+    }
+
+    #[test]
+    fn test_upgrade_golden_replays() {
+        for (version, json) in GOLDEN {
+            let mut state = AppCore::init();
+            for (command, result) in load(json) {
+                if command.is_replayable() {
+                    assert_eq!(AppCore::resolve(&state, command), result, "v{}", version);
+                }
+                AppCore::reducer(&mut state, result);
+            }
+            assert!(matches!(state, AppState::Board(_, _)), "v{}", version);
+        }
+    }
+
+    #[test]
+    fn test_upgrade_first_version() {
+        let records = load(GOLDEN[0].1);
+        assert!(records.iter().all(|(command, _)| command.author.is_empty() && command.rng == RngAlgorithm::Unseeded && !command.is_replayable()));
+        let mut state = AppCore::init();
+        for (_, result) in records {
+            AppCore::reducer(&mut state, result);
+        }
+        // 後から足したフィールドは既定値で埋まる
+        let (board, profiles) = match state {
+            AppState::Board(board, profiles) => (board, profiles),
+            state => panic!("{:?}", state),
+        };
+        assert_eq!(profiles.host, "");
+        assert_eq!(board.guess_rule.limit, None);
+        assert!(board.guesses.is_empty() && board.trades.is_empty());
+        assert!(board.players.values().all(|player| player.role.is_none() && player.guesses_left.is_none() && player.score == 0 && player.knowledges.revealed.is_empty()));
+    }
+
+    #[test]
+    fn test_current_format_is_stable() {
+        // 今の形式を変えたらバージョンを上げて、新しいgoldenを足す
+        let (version, json) = GOLDEN[GOLDEN.len() - 1];
+        assert_eq!(version, SCHEMA_VERSION);
+        let stored: Vec<StoredRecord> = serde_json::from_str(json).unwrap();
+        let written: Vec<StoredRecord> = load(json)
+            .iter()
+            .map(|(command, result)| StoredRecord::new(command, result).unwrap())
+            .collect();
+        assert_eq!(written, stored);
+    }
+
+    #[test]
+    fn test_reject_unknown_version() {
+        let command = AuthoredCommand::new("member-0".to_string(), AppCommand::TakeSnapshot);
+        let mut record = StoredRecord::new(&command, &AppResult::Rejected(crate::model::InvalidCommand::Phase)).unwrap();
+        record.version = SCHEMA_VERSION + 1;
+        assert!(matches!(record.upgrade(), Err(MigrationError::UnknownVersion(_))));
+    }
+}
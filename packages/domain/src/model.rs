@@ -1,36 +1,155 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug,Serialize,Deserialize,Clone)]
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
+/// どの端末でも同じ順に並び、同じ形にシリアライズされるようにBTreeMapで持つ
 pub struct BoardState {
-    pub hints: HashMap<HintId,Hint>,
-    pub players: HashMap<PlayerId,Player>,
+    pub hints: BTreeMap<HintId,Hint>,
+    pub players: BTreeMap<PlayerId,Player>,
+    pub guess_rule: GuessRule,
+    pub guesses: Vec<Guess>,
+    pub trades: Vec<Trade>,
+    /// 盤面ができたコマンドを送った端末の時刻（ミリ秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
 }
 
-pub type Hints =  HashMap<HintId,Hint>;
+pub type Hints =  BTreeMap<HintId,Hint>;
 
-#[derive(Eq,Hash,Clone,PartialEq,Debug,Serialize,Deserialize)]
+#[derive(Eq,Hash,Clone,PartialEq,PartialOrd,Ord,Debug,Serialize,Deserialize)]
 pub struct PlayerId(pub usize);
 
-#[derive(Debug,Serialize,Deserialize,Clone)]
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
 pub struct Player {
     pub password: String,
     pub hints: Vec<HintId>,
     pub target: PlayerId,
-    pub knowledges: PlayerKnowledges
+    pub knowledges: PlayerKnowledges,
+    pub role: Option<Role>,
+    pub ability_used: bool,
+    pub guarded: bool,
+    /// Noneなら無制限
+    pub guesses_left: Option<usize>,
+    pub score: i32,
+    /// 他のプレイヤーがあと何回推理するまで推理できないか
+    pub cooldown: usize,
+    pub found: bool,
+    pub eliminated: bool
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
+pub struct GuessRule {
+    /// 1人あたりの推理回数。Noneなら無制限
+    pub limit: Option<usize>,
+    pub penalty: Penalty,
+    /// 推理回数を使い切ったプレイヤーを脱落させるか
+    pub elimination: bool
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
+pub enum Penalty {
+    None,
+    Score(i32),
+    Cooldown(usize)
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
+pub struct Guess {
+    pub guesser: PlayerId,
+    pub suspect: PlayerId,
+    pub outcome: GuessOutcome,
+    /// 推理を送った端末の時刻（ミリ秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<u64>
 }
 
-#[derive(Debug,Serialize,Deserialize,Clone)]
+#[derive(Eq,PartialEq,Debug,Serialize,Deserialize,Clone)]
+pub enum GuessOutcome {
+    Found,
+    Wrong,
+    /// Guardianに防がれた
+    Blocked
+}
+
+#[derive(Eq,Clone,PartialEq,Debug,Serialize,Deserialize)]
+pub enum Role {
+    /// 追加でターゲットのヒントを1つ知ることができる
+    Detective,
+    /// 自分のヒントの一部が他人のものにすり替えられている
+    Decoy,
+    /// 1人を選んで、その人への推理を1度だけ防ぐことができる
+    Guardian
+}
+
+#[derive(Clone,Debug,Serialize,Deserialize,PartialEq)]
+pub enum Ability {
+    Investigate,
+    Guard(PlayerId)
+}
+
+#[derive(Clone,Debug,Serialize,Deserialize,PartialEq)]
+pub enum AbilityEffect {
+    Investigate(Option<HintId>),
+    Guard(PlayerId)
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
 pub struct PlayerKnowledges {
     pub target: HintId,
-    pub others:  Vec<HintId>
+    pub others:  Vec<HintId>,
+    pub revealed: Vec<HintId>
 }
 
-#[derive(Eq,Hash,Clone,PartialEq,Debug,Serialize,Deserialize)]
+#[derive(Eq,Hash,Clone,PartialEq,PartialOrd,Ord,Debug,Serialize,Deserialize)]
 pub struct HintId(pub usize);
 
-#[derive(Debug,Serialize,Deserialize,Clone)]
+/// BoardState.tradesのindex
+#[derive(Eq,Hash,Clone,PartialEq,Debug,Serialize,Deserialize)]
+pub struct TradeId(pub usize);
+
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
+pub struct Trade {
+    pub from: PlayerId,
+    pub to: PlayerId,
+    /// fromが渡すヒント
+    pub offer: HintId,
+    /// toから受け取るヒント
+    pub request: HintId,
+    pub status: TradeStatus
+}
+
+#[derive(Eq,PartialEq,Debug,Serialize,Deserialize,Clone)]
+pub enum TradeStatus {
+    Pending,
+    Accepted,
+    Declined,
+    /// 承認される前にヒントが手放された
+    Cancelled
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
+pub enum TradeEvent {
+    Proposed(Trade),
+    Accepted(TradeId),
+    Declined(TradeId),
+    Cancelled(TradeId)
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
 pub struct Hint {
     pub text: String
-}
\ No newline at end of file
+}
+
+/// 受け付けられなかったコマンドの理由
+#[derive(Eq,PartialEq,Debug,Serialize,Deserialize,Clone)]
+pub enum InvalidCommand {
+    /// 今のフェーズでは受け付けない
+    Phase,
+    UnknownPlayer,
+    UnknownTrade,
+    /// 入力やプレイヤーの状態が条件を満たしていない
+    NotAllowed,
+    /// 送った人にはそのコマンドを送る権限がない
+    Forbidden
+}
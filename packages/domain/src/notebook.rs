@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::model::{HintId, PlayerId};
+
+/// 手元のヒントごとに、誰がターゲットかを推理したメモ。端末のみに保存する
+#[derive(Debug,Serialize,Deserialize,Clone,Default)]
+pub struct Notebook {
+    pub marks: HashMap<PlayerId,HashMap<HintId,Mark>>
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
+pub enum Mark {
+    Confirmed,
+    Suspect,
+    RuledOut
+}
+
+impl Notebook {
+    pub fn get(&self, player: &PlayerId, hint: &HintId) -> Option<&Mark> {
+        self.marks.get(player).and_then(|marks| marks.get(hint))
+    }
+
+    /// 未記入 → あやしい → 確定 → ちがう → 未記入 の順に切り替える
+    pub fn toggle(&mut self, player: PlayerId, hint: HintId) {
+        let marks = self.marks.entry(player).or_default();
+        let next = match marks.get(&hint) {
+            None => Some(Mark::Suspect),
+            Some(Mark::Suspect) => Some(Mark::Confirmed),
+            Some(Mark::Confirmed) => Some(Mark::RuledOut),
+            Some(Mark::RuledOut) => None,
+        };
+        match next {
+            Some(mark) => marks.insert(hint, mark),
+            None => marks.remove(&hint),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::{HintId, PlayerId};
+
+    use super::{Mark, Notebook};
+
+    #[test]
+    fn test_toggle() {
+        let mut notebook = Notebook::default();
+        let expected = [Some(Mark::Suspect), Some(Mark::Confirmed), Some(Mark::RuledOut), None];
+        for mark in expected.iter() {
+            notebook.toggle(PlayerId(1), HintId(2));
+            assert_eq!(notebook.get(&PlayerId(1), &HintId(2)), mark.as_ref());
+        }
+        assert_eq!(notebook.get(&PlayerId(2), &HintId(2)), None);
+    }
+
+    #[test]
+    fn test_json() {
+        let mut notebook = Notebook::default();
+        notebook.toggle(PlayerId(1), HintId(2));
+        let json = serde_json::to_string(&notebook).unwrap();
+        let notebook: Notebook = serde_json::from_str(json.as_str()).unwrap();
+        assert_eq!(notebook.get(&PlayerId(1), &HintId(2)), Some(&Mark::Suspect));
+    }
+}
@@ -1,16 +1,83 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use serde::{Serialize, Deserialize};
 
 use crate::model::PlayerId;
 
-#[derive(Debug,Serialize,Deserialize,Clone)]
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
 pub struct PlayerProfile {
     pub id: PlayerId,
     pub display_name: String,
 }
 
-#[derive(Debug,Serialize,Deserialize,Clone)]
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
 pub struct Profiles {
-    pub players: HashMap<String,PlayerProfile>
+    /// メンバーのidごとのプロフィール
+    pub players: BTreeMap<String,PlayerProfile>,
+    /// ホストのメンバーのid
+    pub host: String
+}
+
+/// 始める前に集まっているメンバー
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
+pub struct Lobby {
+    /// 最初に参加したメンバーのid
+    pub host: String,
+    /// 参加した順
+    pub members: Vec<LobbyMember>
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone,PartialEq)]
+pub struct LobbyMember {
+    pub id: String,
+    pub name: String
+}
+
+impl Lobby {
+    pub fn contains(&self, member_id: &str) -> bool {
+        self.members.iter().any(|member| member.id == member_id)
+    }
+
+    /// 今いるメンバーで、参加した順にPlayerIdを振る
+    pub fn to_profiles(&self) -> Profiles {
+        Profiles::from_roster(
+            self.members.iter().map(|member| (member.id.clone(), member.name.clone())),
+            self.host.clone()
+        )
+    }
+}
+
+impl Profiles {
+    /// 参加した順に並んだ(メンバーのid, 表示名)から、その順にPlayerIdを振る
+    pub fn from_roster<I: IntoIterator<Item = (String, String)>>(roster: I, host: String) -> Self {
+        Self {
+            players: roster
+                .into_iter()
+                .enumerate()
+                .map(|(index, (member_id, display_name))| (member_id, PlayerProfile { id: PlayerId(index), display_name }))
+                .collect(),
+            host
+        }
+    }
+
+    pub fn find(&self, id: &PlayerId) -> Option<&PlayerProfile> {
+        self.players.values().find(|profile| &profile.id == id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::PlayerId;
+
+    use super::Profiles;
+
+    #[test]
+    fn test_player_id_follows_join_order() {
+        // メンバーのidの並びとは関係なく、参加した順
+        let roster = ["zzz", "aaa", "mmm"].iter().map(|id| (id.to_string(), id.to_uppercase()));
+        let profiles = Profiles::from_roster(roster, "aaa".to_string());
+        let ids: Vec<_> = ["zzz", "aaa", "mmm"].iter().map(|id| profiles.players[*id].id.clone()).collect();
+        assert_eq!(ids, vec![PlayerId(0), PlayerId(1), PlayerId(2)]);
+        assert_eq!(profiles.find(&PlayerId(2)).map(|profile| profile.display_name.as_str()), Some("MMM"));
+    }
 }
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+/// seq_noつきで届いたものを並べ直し、0から抜けなく続いている分だけを取り出す
+pub struct Sequencer<T> {
+    next: usize,
+    /// nextより先のseq_noで、先に届いたもの
+    pending: BTreeMap<usize, T>,
+}
+
+impl <T> Default for Sequencer<T> {
+    fn default() -> Self {
+        Self { next: 0, pending: BTreeMap::new() }
+    }
+}
+
+impl <T> Sequencer<T> {
+    /// 次に取り出すseq_no
+    pub fn next(&self) -> usize {
+        self.next
+    }
+
+    /// スナップショットから読み込むときに、それより前を飛ばす。
+    /// まだ何も取り出していないときだけ使える
+    pub fn start_at(&mut self, seq_no: usize) {
+        debug_assert_eq!(self.next, 0);
+        self.next = seq_no;
+        self.pending = self.pending.split_off(&seq_no);
+    }
+
+    /// 取り出し済みのseq_noは重複として捨てる
+    pub fn receive<I: IntoIterator<Item = (usize, T)>>(&mut self, items: I) -> Vec<T> {
+        for (seq_no, item) in items {
+            if seq_no >= self.next {
+                self.pending.entry(seq_no).or_insert(item);
+            }
+        }
+        let mut ready = Vec::new();
+        while let Some(item) = self.pending.remove(&self.next) {
+            ready.push(item);
+            self.next += 1;
+        }
+        ready
+    }
+
+    /// 先のseq_noが届いているのに、間のものが届いていない
+    pub fn has_gap(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sequencer;
+
+    #[test]
+    fn test_in_order() {
+        let mut sequencer = Sequencer::default();
+        assert_eq!(sequencer.receive([(0, "a"), (1, "b")]), vec!["a", "b"]);
+        assert_eq!(sequencer.receive([(2, "c")]), vec!["c"]);
+        assert_eq!(sequencer.next(), 3);
+        assert!(!sequencer.has_gap());
+    }
+
+    #[test]
+    fn test_reordered_in_a_batch() {
+        let mut sequencer = Sequencer::default();
+        assert_eq!(sequencer.receive([(1, "b"), (2, "c"), (0, "a")]), vec!["a", "b", "c"]);
+        assert!(!sequencer.has_gap());
+    }
+
+    #[test]
+    fn test_reordered_between_batches() {
+        let mut sequencer = Sequencer::default();
+        assert_eq!(sequencer.receive([(1, "b")]), Vec::<&str>::new());
+        assert!(sequencer.has_gap());
+        assert_eq!(sequencer.receive([(0, "a")]), vec!["a", "b"]);
+        assert!(!sequencer.has_gap());
+    }
+
+    #[test]
+    fn test_gap_is_filled_by_refetch() {
+        let mut sequencer = Sequencer::default();
+        assert_eq!(sequencer.receive([(0, "a")]), vec!["a"]);
+        // 1が抜けたまま2と3が届く
+        assert_eq!(sequencer.receive([(2, "c"), (3, "d")]), Vec::<&str>::new());
+        assert!(sequencer.has_gap());
+        assert_eq!(sequencer.next(), 1);
+        // next以降を取り直すと、届いていたものも含めて返ってくる
+        assert_eq!(sequencer.receive([(1, "b"), (2, "c"), (3, "d")]), vec!["b", "c", "d"]);
+        assert!(!sequencer.has_gap());
+    }
+
+    #[test]
+    fn test_start_at_snapshot() {
+        let mut sequencer = Sequencer::default();
+        sequencer.start_at(50);
+        assert_eq!(sequencer.receive([(51, "b"), (50, "a"), (3, "x")]), vec!["a", "b"]);
+        assert!(!sequencer.has_gap());
+    }
+
+    #[test]
+    fn test_duplicates_are_ignored() {
+        let mut sequencer = Sequencer::default();
+        assert_eq!(sequencer.receive([(0, "a"), (1, "b")]), vec!["a", "b"]);
+        // 購読し直したときのように、最初から届き直す
+        assert_eq!(sequencer.receive([(0, "a"), (1, "b"), (2, "c")]), vec!["c"]);
+        assert_eq!(sequencer.receive([(2, "x")]), Vec::<&str>::new());
+        assert!(!sequencer.has_gap());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::state::AppResult;
+
+/// このレコード数ごとにスナップショットを残す
+pub const SNAPSHOT_INTERVAL: usize = 50;
+
+/// 次のseq_noにスナップショットを置くべきか
+pub fn is_snapshot_due(next_seq_no: usize) -> bool {
+    next_seq_no > 0 && next_seq_no.is_multiple_of(SNAPSHOT_INTERVAL)
+}
+
+/// 届いたレコードのうち、最新のスナップショットのseq_no
+pub fn latest_snapshot<'a, I: IntoIterator<Item = (usize, &'a AppResult)>>(records: I) -> Option<usize> {
+    records
+        .into_iter()
+        .filter(|(_, result)| matches!(result, AppResult::Snapshot(_)))
+        .map(|(seq_no, _)| seq_no)
+        .max()
+}
+
+#[cfg(test)]
+mod test {
+    use exprocess::core::ExprocessCore;
+
+    use crate::{
+        fixture::{authored, input, profiles},
+        model::{PlayerId, Role},
+        state::{AppCommand, AppCore, AppResult, AppState, Setting},
+    };
+
+    use super::{is_snapshot_due, latest_snapshot};
+
+    fn dispatch(state: &mut AppState, author: usize, command: AppCommand) -> AppResult {
+        let result = AppCore::resolve(state, authored(author, command));
+        AppCore::reducer(state, result.clone());
+        result
+    }
+
+    fn round_trip(state: &AppState) -> AppState {
+        serde_json::from_str(serde_json::to_string(state).unwrap().as_str()).unwrap()
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut state = AppCore::init();
+        assert_eq!(round_trip(&state), state);
+        dispatch(&mut state, 0, AppCommand::InitProfile(profiles(3)));
+        if let AppState::StandbyPassword(_, _, setting) = &mut state {
+            *setting = Setting { roles: vec![Role::Detective, Role::Decoy], ..Setting::recommend() };
+        }
+        for n in 0..3 {
+            dispatch(&mut state, n, AppCommand::PushPassword(input(n)));
+            assert_eq!(round_trip(&state), state);
+        }
+        dispatch(&mut state, 1, AppCommand::Guess(PlayerId(1), PlayerId(2)));
+        assert!(matches!(state, AppState::Board(_, _)));
+        assert_eq!(round_trip(&state), state);
+    }
+
+    #[test]
+    fn test_snapshot_restores_state() {
+        let mut state = AppCore::init();
+        assert!(matches!(dispatch(&mut state, 0, AppCommand::TakeSnapshot), AppResult::Rejected(_)));
+        dispatch(&mut state, 0, AppCommand::Join { name: "0".to_string() });
+        // ロビーでも参加していない人は残せない
+        assert!(matches!(dispatch(&mut state, 1, AppCommand::TakeSnapshot), AppResult::Rejected(_)));
+        assert!(matches!(dispatch(&mut state, 0, AppCommand::TakeSnapshot), AppResult::Snapshot(_)));
+        let mut state = AppCore::init();
+        dispatch(&mut state, 0, AppCommand::InitProfile(profiles(2)));
+        let snapshot = dispatch(&mut state, 1, AppCommand::TakeSnapshot);
+        // スナップショットは状態を変えない
+        assert!(matches!(&snapshot, AppResult::Snapshot(taken) if **taken == state));
+        let mut restored = AppCore::init();
+        AppCore::reducer(&mut restored, serde_json::from_str(serde_json::to_string(&snapshot).unwrap().as_str()).unwrap());
+        assert_eq!(restored, state);
+        // 参加者以外は残せない
+        assert!(matches!(dispatch(&mut state, 2, AppCommand::TakeSnapshot), AppResult::Rejected(_)));
+    }
+
+    #[test]
+    fn test_snapshot_policy() {
+        assert!(!is_snapshot_due(0));
+        assert!(!is_snapshot_due(49));
+        assert!(is_snapshot_due(50));
+        let snapshot = AppResult::Snapshot(Box::new(AppState::Blank));
+        let other = AppResult::Rejected(crate::model::InvalidCommand::Phase);
+        assert_eq!(latest_snapshot([(50, &snapshot), (51, &other), (100, &snapshot)]), Some(100));
+        assert_eq!(latest_snapshot([(3, &other)]), None);
+    }
+}
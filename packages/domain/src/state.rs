@@ -1,17 +1,113 @@
-use core::panic;
+use std::collections::HashSet;
 
 use exprocess::core::ExprocessCore;
-use rand::thread_rng;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::{ChaCha8Rng, ChaCha12Rng};
 use serde::{Serialize, Deserialize};
 
-use crate::{model::{BoardState}, function::{InitBoard, init, InitPlayer}, profile::{Profiles}};
+use crate::{model::{BoardState, PlayerId, HintId, TradeId, TradeEvent, Ability, AbilityEffect, Role, GuessRule, Penalty, Guess, InvalidCommand}, function::{InitBoard, init, InitPlayer, use_ability, apply_ability, guess, apply_guess, propose_trade, answer_trade, apply_trade}, profile::{Lobby, LobbyMember, Profiles}, checksum::fnv1a};
 
 pub struct AppCore;
 
+/// 2人以上で、PlayerIdに重複がなく、ホストが参加していること
+fn validate_profiles(profiles: &Profiles) -> Result<(),InvalidCommand> {
+    let ids: HashSet<_> = profiles.players.values().map(|profile| &profile.id).collect();
+    if profiles.players.len() < 2 || ids.len() != profiles.players.len() || !profiles.players.contains_key(&profiles.host) {
+        return Err(InvalidCommand::NotAllowed)
+    }
+    Ok(())
+}
+
+fn validate_name(name: &str) -> Result<(),InvalidCommand> {
+    if name.trim().is_empty() {
+        return Err(InvalidCommand::NotAllowed)
+    }
+    Ok(())
+}
+
+fn validate_password(profiles: &Profiles, inputs: &[InitPlayer], setting: &Setting, input: &InitPlayer) -> Result<(),InvalidCommand> {
+    profiles.find(&input.id).ok_or(InvalidCommand::UnknownPlayer)?;
+    if inputs.iter().any(|pushed| pushed.id == input.id) || input.hints.len() != setting.hints_num {
+        return Err(InvalidCommand::NotAllowed)
+    }
+    Ok(())
+}
+
+/// ホストだけが送れるコマンドと、本人だけが送れるコマンドを確かめる。
+/// InitProfileはそこで宣言したホスト本人しか送れない。
+/// authorは送った端末が自分で書くので、これは正直なクライアント同士の取り決めでしかない。
+/// 書き換えたクライアントが他人やホストになりすますのは防げない
+fn authorize(state: &AppState, author: &str, command: &AppCommand) -> Result<(),InvalidCommand> {
+    let allowed = match (state, command) {
+        (_, AppCommand::InitProfile(profiles)) => profiles.host == author,
+        // 参加者が決まる前のコマンドは、フェーズが違うので後で拒否される
+        (AppState::Blank, _) => true,
+        (AppState::Lobby(lobby), AppCommand::Start) => lobby.host == author,
+        (AppState::Lobby(lobby), AppCommand::TakeSnapshot) => lobby.contains(author),
+        // 参加と退出は送った本人のこと
+        (AppState::Lobby(_), _) => true,
+        (_, AppCommand::Join { .. } | AppCommand::Leave | AppCommand::Start) => true,
+        (AppState::StandbyPassword(profiles,_,_), AppCommand::ChooseRoles(_)) => profiles.host == author,
+        (AppState::StandbyPassword(profiles,_,_) | AppState::Board(_,profiles), AppCommand::TakeSnapshot) => profiles.players.contains_key(author),
+        (AppState::StandbyPassword(profiles,_,_) | AppState::Board(_,profiles), command) => {
+            profiles.players.get(author).is_some_and(|profile| Some(&profile.id) == command.actor())
+        },
+    };
+    if allowed { Ok(()) } else { Err(InvalidCommand::Forbidden) }
+}
+
+/// 誰がresolveしても同じ結果になるように、入力から乱数の種を決める。
+/// randの版が変わっても同じ列になるアルゴリズムを固定で使う
+fn seeded_rng<T: Serialize>(algorithm: RngAlgorithm, input: &T) -> SeededRng {
+    let json = serde_json::to_string(input).expect("commands and board state always serialize to JSON");
+    let seed = fnv1a(json.as_bytes());
+    match algorithm {
+        // rand 0.8のStdRngの中身。Unseededのレコードはresolveし直さない
+        RngAlgorithm::Unseeded | RngAlgorithm::StdRng => SeededRng::ChaCha12(ChaCha12Rng::seed_from_u64(seed)),
+        RngAlgorithm::ChaCha8 => SeededRng::ChaCha8(ChaCha8Rng::seed_from_u64(seed)),
+    }
+}
+
+#[derive(Clone)]
+enum SeededRng {
+    ChaCha12(ChaCha12Rng),
+    ChaCha8(ChaCha8Rng)
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            SeededRng::ChaCha12(rng) => rng.next_u32(),
+            SeededRng::ChaCha8(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            SeededRng::ChaCha12(rng) => rng.next_u64(),
+            SeededRng::ChaCha8(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            SeededRng::ChaCha12(rng) => rng.fill_bytes(dest),
+            SeededRng::ChaCha8(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            SeededRng::ChaCha12(rng) => rng.try_fill_bytes(dest),
+            SeededRng::ChaCha8(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
 impl ExprocessCore for AppCore {
     type State = AppState;
 
-    type Command = AppCommand;
+    type Command = AuthoredCommand;
 
     type Result = AppResult;
 
@@ -19,78 +115,514 @@ impl ExprocessCore for AppCore {
         AppState::Blank
     }
 
-    fn resolve(state: &Self::State, command: Self::Command) -> Self::Result {
-        match (state,command) {
-            (AppState::Blank, AppCommand::InitProfile(profiles)) => AppResult::InitProfile(profiles),
-            (AppState::Blank, _) => panic!(),
-            (AppState::StandbyPassword(profiles,inputs,setting), AppCommand::PushPassword(input)) => {
-                if inputs.len() + 1 < profiles.players.len() {
-                    return AppResult::PushPassword(input)
+    fn resolve(state: &Self::State, AuthoredCommand { author, command, at, rng }: Self::Command) -> Self::Result {
+        let result = authorize(state,author.as_str(),&command).and_then(|_| match (state,command) {
+            (AppState::Blank, AppCommand::InitProfile(profiles)) => validate_profiles(&profiles).map(|_| AppResult::InitProfile(profiles)),
+            (AppState::Blank, AppCommand::Join { name }) => validate_name(&name).map(|_| AppResult::Joined(LobbyMember { id: author, name })),
+            (AppState::Lobby(lobby), AppCommand::Join { name }) => {
+                if lobby.contains(&author) {
+                    return Err(InvalidCommand::NotAllowed)
                 }
-                let inputs: Vec<_> = inputs.iter().cloned().chain([input].into_iter()).collect();
-                let board = init(InitBoard {
-                    players: inputs,
-                    hints_num: setting.hints_num,
-                },&mut thread_rng());
-                AppResult::InitBoard(board)
-            },
-            (AppState::StandbyPassword(_, _, _), _) => panic!(),
-            (AppState::Board(_board,_profiles), AppCommand::InitProfile(_)) => todo!(),
-            (AppState::Board(_, _), AppCommand::PushPassword(_)) => todo!(),
-        }
+                validate_name(&name).map(|_| AppResult::Joined(LobbyMember { id: author, name }))
+            },
+            (AppState::Lobby(lobby), AppCommand::Leave) => {
+                // ホストが抜けると誰も始められない
+                if !lobby.contains(&author) || lobby.host == author {
+                    return Err(InvalidCommand::NotAllowed)
+                }
+                Ok(AppResult::Left(author))
+            },
+            (AppState::Lobby(lobby), AppCommand::Start) => {
+                let profiles = lobby.to_profiles();
+                validate_profiles(&profiles).map(|_| AppResult::InitProfile(profiles))
+            },
+            (AppState::StandbyPassword(profiles,inputs,setting), AppCommand::PushPassword(input)) => {
+                validate_password(profiles,inputs,setting,&input).map(|_| {
+                    if inputs.len() + 1 < profiles.players.len() {
+                        return AppResult::PushPassword(input)
+                    }
+                    let inputs: Vec<_> = inputs.iter().cloned().chain([input]).collect();
+                    let mut rng = seeded_rng(rng,&inputs);
+                    let mut board = init(InitBoard {
+                        players: inputs,
+                        hints_num: setting.hints_num,
+                        roles: setting.roles.clone(),
+                        guess_rule: setting.guess_rule.clone(),
+                    },&mut rng);
+                    board.started_at = at;
+                    AppResult::InitBoard(board)
+                })
+            },
+            (AppState::StandbyPassword(..), AppCommand::ChooseRoles(roles)) => Ok(AppResult::ChooseRoles(roles)),
+            (AppState::Board(board,_), AppCommand::UseAbility(id,ability)) => {
+                let mut rng = seeded_rng(rng,&(&id,&board.guesses,&board.trades));
+                use_ability(board,&id,ability,&mut rng).map(|effect| AppResult::UseAbility(id,effect))
+            },
+            (AppState::Board(board,_), AppCommand::Guess(guesser,suspect)) => guess(board,guesser,suspect).map(|guess| AppResult::Guess(Guess { at, ..guess })),
+            (AppState::Board(board,_), AppCommand::ProposeTrade { from, to, offer, request }) => propose_trade(board,from,to,offer,request).map(AppResult::Trade),
+            (AppState::Board(board,_), AppCommand::AcceptTrade(player,trade)) => answer_trade(board,&player,trade,true).map(AppResult::Trade),
+            (AppState::Board(board,_), AppCommand::DeclineTrade(player,trade)) => answer_trade(board,&player,trade,false).map(AppResult::Trade),
+            // 何も起きていない部屋のスナップショットはいらない
+            (AppState::Blank, AppCommand::TakeSnapshot) => Err(InvalidCommand::Phase),
+            (state, AppCommand::TakeSnapshot) => Ok(AppResult::Snapshot(Box::new(state.clone()))),
+            (_, _) => Err(InvalidCommand::Phase),
+        });
+        result.unwrap_or_else(AppResult::Rejected)
     }
 
     fn reducer(mut state: &mut Self::State, result: Self::Result) {
         match (&mut state,result) {
+            (_, AppResult::Rejected(_)) => {},
+            (_, AppResult::Snapshot(snapshot)) => {
+                *state = *snapshot;
+            },
             (AppState::Blank, AppResult::InitProfile(profiles)) => {
                 let len = profiles.players.len();
                 *state = AppState::StandbyPassword(profiles,Vec::with_capacity(len),Setting::recommend());
             },
-            (AppState::Blank,_) => panic!(),
+            (AppState::Blank, AppResult::Joined(member)) => {
+                *state = AppState::Lobby(Lobby { host: member.id.clone(), members: vec![member] });
+            },
+            // 段階に合わない結果は、反映しても意味がないので読み飛ばす
+            (AppState::Blank,_) => {},
+            (AppState::Lobby(lobby), AppResult::Joined(member)) => {
+                lobby.members.push(member);
+            },
+            (AppState::Lobby(lobby), AppResult::Left(member_id)) => {
+                lobby.members.retain(|member| member.id != member_id);
+            },
+            (AppState::Lobby(_), AppResult::InitProfile(profiles)) => {
+                let len = profiles.players.len();
+                *state = AppState::StandbyPassword(profiles,Vec::with_capacity(len),Setting::recommend());
+            },
+            (AppState::Lobby(_), _) => {},
             (AppState::StandbyPassword(profiles,_,_), AppResult::InitBoard(board)) => {
                 *state = AppState::Board(board,profiles.clone());
             },
             (AppState::StandbyPassword(_,inputs,_), AppResult::PushPassword(input)) => {
                 inputs.push(input);
             },
-            (AppState::StandbyPassword(_,_,_), _) => panic!() ,
-            (AppState::Board(_, _), AppResult::InitProfile(_)) => todo!(),
-            (AppState::Board(_, _), AppResult::PushPassword(_)) => todo!(),
-            (AppState::Board(_, _), AppResult::InitBoard(_)) => todo!(),
+            (AppState::StandbyPassword(_,_,setting), AppResult::ChooseRoles(roles)) => {
+                setting.roles = roles;
+            },
+            (AppState::StandbyPassword(_,_,_), _) => {},
+            (AppState::Board(board, _), AppResult::UseAbility(id,effect)) => {
+                apply_ability(board,&id,effect);
+            },
+            (AppState::Board(board, _), AppResult::Guess(guess)) => {
+                apply_guess(board,guess);
+            },
+            (AppState::Board(board, _), AppResult::Trade(event)) => {
+                apply_trade(board,event);
+            },
+            (AppState::Board(_, _), _) => {},
         }
     }
 }
 
-#[derive(Serialize,Deserialize,Clone)]
+/// 送ったメンバーのidつきのコマンド
+#[derive(Serialize,Deserialize,Clone,Debug)]
+pub struct AuthoredCommand {
+    pub author: String,
+    pub command: AppCommand,
+    /// 送った端末の時刻（ミリ秒）。遊んだ時間を出すのに使う
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<u64>,
+    /// resolveで使う乱数。記録されたときのschemaのバージョンで決まる
+    #[serde(default)]
+    pub rng: RngAlgorithm
+}
+
+impl AuthoredCommand {
+    /// 時刻を持たないコマンド
+    pub fn new(author: String, command: AppCommand) -> Self {
+        Self { author, command, at: None, rng: RngAlgorithm::ChaCha8 }
+    }
+
+    /// resolveし直せば、記録された結果と同じになるはずのもの
+    pub fn is_replayable(&self) -> bool {
+        self.rng != RngAlgorithm::Unseeded
+    }
+}
+
+/// resolveで配ったり選んだりするときの乱数のアルゴリズム。
+/// 同じレコードからは誰がいつresolveしても同じ結果になるように、一度使ったものは変えない
+#[derive(Serialize,Deserialize,Clone,Copy,Debug,PartialEq,Eq,Default)]
+pub enum RngAlgorithm {
+    /// 最初の形式のレコード。thread_rngで配っていて送り主も無いので、resolveし直しても同じにならない
+    Unseeded,
+    /// schema 2までのレコード。rand 0.8のStdRngで配っていた
+    #[default]
+    StdRng,
+    ChaCha8
+}
+
+/// 保存される形式。変えるときはmigrationでschemaのバージョンを上げる
+#[derive(Serialize,Deserialize,Clone,Debug)]
+#[serde(tag = "type", content = "value")]
 pub enum AppCommand {
     InitProfile(Profiles),
-    PushPassword(InitPlayer)
+    PushPassword(InitPlayer),
+    UseAbility(PlayerId,Ability),
+    /// 推理する人、ターゲットだと思う人
+    Guess(PlayerId,PlayerId),
+    ProposeTrade {
+        from: PlayerId,
+        to: PlayerId,
+        offer: HintId,
+        request: HintId
+    },
+    /// 承認する人、交換
+    AcceptTrade(PlayerId,TradeId),
+    DeclineTrade(PlayerId,TradeId),
+    /// その時点の状態をレコードとして残す。誰でも送れる
+    TakeSnapshot,
+    /// 送った人が参加する。最初に参加した人がホストになる
+    Join {
+        name: String
+    },
+    /// 送った人が始める前に抜ける
+    Leave,
+    /// ホストが、今いるメンバーで始める
+    Start,
+    /// ホストが、あいことばの入力中に配る役職を決める
+    ChooseRoles(Vec<Role>)
+}
+
+impl AppCommand {
+    /// 誰として操作するコマンドか。プレイヤーとして操作しないものはNone
+    pub fn actor(&self) -> Option<&PlayerId> {
+        match self {
+            AppCommand::InitProfile(_) | AppCommand::TakeSnapshot => None,
+            AppCommand::Join { .. } | AppCommand::Leave | AppCommand::Start | AppCommand::ChooseRoles(_) => None,
+            AppCommand::PushPassword(input) => Some(&input.id),
+            AppCommand::UseAbility(id,_) => Some(id),
+            AppCommand::Guess(guesser,_) => Some(guesser),
+            AppCommand::ProposeTrade { from, .. } => Some(from),
+            AppCommand::AcceptTrade(id,_) => Some(id),
+            AppCommand::DeclineTrade(id,_) => Some(id),
+        }
+    }
 }
 
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
 pub enum AppState {
     Blank,
+    Lobby(Lobby),
     StandbyPassword(Profiles,Vec<InitPlayer>,Setting),
     Board(BoardState,Profiles)
 }
 
-#[derive(Clone)]
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
 pub struct Setting {
-    pub hints_num: usize
+    pub hints_num: usize,
+    /// 配る役職。人数より多い分は配られない
+    pub roles: Vec<Role>,
+    pub guess_rule: GuessRule
 }
 
 impl Setting {
     pub fn recommend() -> Self {
         Self {
-            hints_num: 3
+            hints_num: 3,
+            roles: Vec::new(),
+            guess_rule: GuessRule {
+                limit: Some(3),
+                penalty: Penalty::Score(1),
+                elimination: false
+            }
         }
     }
 }
 
 
-#[derive(Serialize,Deserialize,Clone)]
+/// 保存される形式。変えるときはmigrationでschemaのバージョンを上げる
+#[derive(Serialize,Deserialize,Clone,Debug,PartialEq)]
+#[serde(tag = "type", content = "value")]
 pub enum AppResult {
     InitProfile(Profiles),
     PushPassword(InitPlayer),
-    InitBoard(BoardState)
+    InitBoard(BoardState),
+    UseAbility(PlayerId,AbilityEffect),
+    Guess(Guess),
+    Trade(TradeEvent),
+    /// 状態を変えない
+    Rejected(InvalidCommand),
+    /// このレコードより前をすべて反映した状態。ここから読み込めば前のレコードはいらない
+    Snapshot(Box<AppState>),
+    Joined(LobbyMember),
+    /// 抜けたメンバーのid
+    Left(String),
+    ChooseRoles(Vec<Role>)
 }
 
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use exprocess::core::ExprocessCore;
+    use mytil::validate_no_duplicate;
+    use proptest::{collection::vec, option, prelude::*};
+
+    use crate::{
+        fixture::{self, member_id, profiles},
+        function::InitPlayer,
+        model::{Ability, GuessRule, HintId, InvalidCommand, Penalty, PlayerId, Role, TradeId},
+    };
+
+    use super::{AppCommand, AppCore, AppResult, AppState, AuthoredCommand, Setting};
+
+    /// 操作するプレイヤー本人が送ったことにする
+    fn authored(command: AppCommand) -> AuthoredCommand {
+        fixture::authored(command.actor().map_or(0, |id| id.0), command)
+    }
+
+    fn setting() -> impl Strategy<Value = Setting> {
+        (
+            1usize..4,
+            vec(prop_oneof![Just(Role::Detective), Just(Role::Decoy), Just(Role::Guardian)], 0..4),
+            option::of(0usize..4),
+            prop_oneof![Just(Penalty::None), (0i32..3).prop_map(Penalty::Score), (0usize..3).prop_map(Penalty::Cooldown)],
+            any::<bool>(),
+        )
+            .prop_map(|(hints_num, roles, limit, penalty, elimination)| Setting {
+                hints_num,
+                roles,
+                guess_rule: GuessRule { limit, penalty, elimination },
+            })
+    }
+
+    /// 存在しないPlayerIdやHintIdも混ぜる
+    fn command(players: usize, hints_num: usize) -> impl Strategy<Value = AppCommand> {
+        let player = move || (0..players + 1).prop_map(PlayerId);
+        let hint = move || (0..players * hints_num + 2).prop_map(HintId);
+        prop_oneof![
+            1 => (0usize..4).prop_map(|n| AppCommand::InitProfile(profiles(n))),
+            3 => (player(), "[a-z]{1,3}", vec("[a-z]{1,3}", 0..4)).prop_map(|(id, password, hints)| AppCommand::PushPassword(InitPlayer { id, password, hints })),
+            2 => (player(), option::of(player())).prop_map(|(id, guard)| AppCommand::UseAbility(id, guard.map_or(Ability::Investigate, Ability::Guard))),
+            4 => (player(), player()).prop_map(|(guesser, suspect)| AppCommand::Guess(guesser, suspect)),
+            3 => (player(), player(), hint(), hint()).prop_map(|(from, to, offer, request)| AppCommand::ProposeTrade { from, to, offer, request }),
+            1 => Just(AppCommand::TakeSnapshot),
+            1 => "[a-z]{0,3}".prop_map(|name| AppCommand::Join { name }),
+            1 => Just(AppCommand::Leave),
+            1 => Just(AppCommand::Start),
+            1 => vec(prop_oneof![Just(Role::Detective), Just(Role::Decoy), Just(Role::Guardian)], 0..4).prop_map(AppCommand::ChooseRoles),
+            3 => (player(), 0usize..4, any::<bool>()).prop_map(|(id, trade, accept)| if accept {
+                AppCommand::AcceptTrade(id, TradeId(trade))
+            } else {
+                AppCommand::DeclineTrade(id, TradeId(trade))
+            }),
+        ]
+    }
+
+    /// ヒント入力まで終わらせるコマンドのあとに、ランダムなコマンドを続ける
+    fn scenario() -> impl Strategy<Value = (usize, Setting, Vec<AppCommand>)> {
+        (2usize..6, setting()).prop_flat_map(|(players, setting)| {
+            let setup: Vec<_> = (0..players)
+                .map(|n| AppCommand::PushPassword(InitPlayer {
+                    id: PlayerId(n),
+                    password: n.to_string(),
+                    hints: (0..setting.hints_num).map(|m| format!("{}-{}", n, m)).collect(),
+                }))
+                .collect();
+            let commands = vec(command(players, setting.hints_num), 0..60);
+            (Just(players), Just(setting), Just(setup), commands).prop_map(|(players, setting, mut setup, commands)| {
+                setup.extend(commands);
+                (players, setting, setup)
+            })
+        })
+    }
+
+    fn dispatch(state: &mut AppState, command: AppCommand) -> AppResult {
+        let result = AppCore::resolve(state, authored(command));
+        AppCore::reducer(state, result.clone());
+        result
+    }
+
+    fn assert_invariants(state: &AppState) {
+        let board = match state {
+            AppState::Board(board, _) => board,
+            _ => return,
+        };
+        for (id, player) in board.players.iter() {
+            // 自分をターゲットにしない
+            assert_ne!(id, &player.target);
+            assert!(board.players.contains_key(&player.target));
+            let knowledges = player.knowledges.others.iter().chain(player.knowledges.revealed.iter()).chain([&player.knowledges.target]);
+            assert!(knowledges.chain(player.hints.iter()).all(|hint| board.hints.contains_key(hint)));
+        }
+        // 辞書のヒントはちょうど1人が持ち主になっている
+        let owned: Vec<_> = board.players.values().flat_map(|p| p.hints.iter()).collect();
+        assert!(validate_no_duplicate(owned.iter()));
+        assert_eq!(owned.iter().collect::<HashSet<_>>().len(), board.hints.len());
+        // 配られたヒントは重複しない
+        assert!(validate_no_duplicate(board.players.values().flat_map(|p| p.knowledges.others.iter().chain([&p.knowledges.target]))));
+        assert!(board.trades.iter().all(|trade| board.hints.contains_key(&trade.offer) && board.hints.contains_key(&trade.request)));
+    }
+
+    proptest! {
+        #[test]
+        fn test_random_commands_from_blank(commands in vec(command(4, 3), 0..30)) {
+            let mut state = AppCore::init();
+            for command in commands {
+                dispatch(&mut state, command);
+                assert_invariants(&state);
+            }
+        }
+
+        #[test]
+        fn test_random_commands_on_board((players, setting, commands) in scenario()) {
+            let mut state = AppState::StandbyPassword(profiles(players), Vec::new(), setting);
+            for command in commands {
+                dispatch(&mut state, command);
+                assert_invariants(&state);
+            }
+            prop_assert!(matches!(state, AppState::Board(_, _)));
+        }
+    }
+
+    #[test]
+    fn test_resolve_is_deterministic() {
+        let mut state = AppState::StandbyPassword(profiles(3), Vec::new(), Setting {
+            roles: vec![Role::Detective, Role::Decoy, Role::Guardian],
+            ..Setting::recommend()
+        });
+        let push = |n: usize| AppCommand::PushPassword(InitPlayer {
+            id: PlayerId(n),
+            password: n.to_string(),
+            hints: (0..3).map(|m| format!("{}-{}", n, m)).collect(),
+        });
+        dispatch(&mut state, push(0));
+        dispatch(&mut state, push(1));
+        let board = AppCore::resolve(&state, authored(push(2)));
+        assert!(matches!(board, AppResult::InitBoard(_)));
+        assert_eq!(AppCore::resolve(&state, authored(push(2))), board);
+        AppCore::reducer(&mut state, board);
+        let investigate = |n: usize| AppCommand::UseAbility(PlayerId(n), Ability::Investigate);
+        for n in 0..3 {
+            assert_eq!(AppCore::resolve(&state, authored(investigate(n))), AppCore::resolve(&state, authored(investigate(n))));
+        }
+    }
+
+    #[test]
+    fn test_rejected_command_keeps_state() {
+        let mut state = AppCore::init();
+        assert!(matches!(dispatch(&mut state, AppCommand::Guess(PlayerId(0), PlayerId(1))), AppResult::Rejected(_)));
+        assert!(matches!(dispatch(&mut state, AppCommand::InitProfile(profiles(1))), AppResult::Rejected(_)));
+        assert!(matches!(state, AppState::Blank));
+        assert!(matches!(dispatch(&mut state, AppCommand::InitProfile(profiles(2))), AppResult::InitProfile(_)));
+        let push = |id: usize, hints: usize| AppCommand::PushPassword(InitPlayer {
+            id: PlayerId(id),
+            password: String::new(),
+            hints: vec![String::new(); hints],
+        });
+        assert!(matches!(dispatch(&mut state, push(0, 2)), AppResult::Rejected(_)));
+        assert!(matches!(dispatch(&mut state, push(2, 3)), AppResult::Rejected(_)));
+        assert!(matches!(dispatch(&mut state, push(0, 3)), AppResult::PushPassword(_)));
+        assert!(matches!(dispatch(&mut state, push(0, 3)), AppResult::Rejected(_)));
+        assert!(matches!(dispatch(&mut state, push(1, 3)), AppResult::InitBoard(_)));
+        assert!(matches!(state, AppState::Board(_, _)));
+    }
+
+    #[test]
+    fn test_out_of_phase_result_keeps_state() {
+        let mut state = AppCore::init();
+        AppCore::reducer(&mut state, AppResult::PushPassword(fixture::input(0)));
+        assert_eq!(state, AppState::Blank);
+        let mut state = fixture::replay(fixture::setup(2));
+        let board = state.clone();
+        for result in [
+            AppResult::InitProfile(profiles(3)),
+            AppResult::PushPassword(fixture::input(2)),
+            AppResult::Left(member_id(1)),
+            AppResult::ChooseRoles(vec![Role::Decoy]),
+        ] {
+            AppCore::reducer(&mut state, result);
+            assert_eq!(state, board);
+        }
+    }
+
+    #[test]
+    fn test_lobby_lifecycle() {
+        let join = |name: &str| AppCommand::Join { name: name.to_string() };
+        let mut state = AppCore::init();
+        let mut send = |author: &str, command: AppCommand| {
+            let result = AppCore::resolve(&state, AuthoredCommand::new(author.to_string(), command));
+            AppCore::reducer(&mut state, result.clone());
+            result
+        };
+        assert!(matches!(send("host", join("ホスト")), AppResult::Joined(_)));
+        assert_eq!(send("host", AppCommand::Start), AppResult::Rejected(InvalidCommand::NotAllowed));
+        assert!(matches!(send("late", join("遅刻")), AppResult::Joined(_)));
+        assert!(matches!(send("guest", join("ゲスト")), AppResult::Joined(_)));
+        assert_eq!(send("guest", join("ゲスト")), AppResult::Rejected(InvalidCommand::NotAllowed));
+        assert_eq!(send("stranger", join(" ")), AppResult::Rejected(InvalidCommand::NotAllowed));
+        assert_eq!(send("host", AppCommand::Leave), AppResult::Rejected(InvalidCommand::NotAllowed));
+        assert_eq!(send("late", AppCommand::Leave), AppResult::Left("late".to_string()));
+        // ホストしか始められない
+        assert_eq!(send("guest", AppCommand::Start), AppResult::Rejected(InvalidCommand::Forbidden));
+        let profiles = match send("host", AppCommand::Start) {
+            AppResult::InitProfile(profiles) => profiles,
+            result => panic!("{:?}", result),
+        };
+        // 始めたときにいたメンバーだけが、参加した順に並ぶ
+        assert_eq!(profiles.host, "host");
+        assert_eq!(profiles.players["host"].id, PlayerId(0));
+        assert_eq!(profiles.players["guest"].id, PlayerId(1));
+        assert!(!profiles.players.contains_key("late"));
+        assert_eq!(send("stranger", join("遅刻")), AppResult::Rejected(InvalidCommand::Phase));
+        assert!(matches!(state, AppState::StandbyPassword(_, _, _)));
+    }
+
+    #[test]
+    fn test_choose_roles() {
+        let roles = vec![Role::Detective, Role::Decoy];
+        let mut state = AppCore::init();
+        dispatch(&mut state, AppCommand::InitProfile(profiles(3)));
+        // ホスト以外は役職を決められない
+        let forged = fixture::authored(1, AppCommand::ChooseRoles(roles.clone()));
+        assert_eq!(AppCore::resolve(&state, forged), AppResult::Rejected(InvalidCommand::Forbidden));
+        assert_eq!(dispatch(&mut state, AppCommand::ChooseRoles(roles.clone())), AppResult::ChooseRoles(roles.clone()));
+        assert!(matches!(&state, AppState::StandbyPassword(_, _, setting) if setting.roles == roles));
+        for n in 0..3 {
+            dispatch(&mut state, AppCommand::PushPassword(fixture::input(n)));
+        }
+        let board = match &state {
+            AppState::Board(board, _) => board,
+            state => panic!("{:?}", state),
+        };
+        assert_eq!(board.players.values().filter(|p| p.role.is_some()).count(), 2);
+        assert_eq!(dispatch(&mut state, AppCommand::ChooseRoles(Vec::new())), AppResult::Rejected(InvalidCommand::Forbidden));
+    }
+
+    #[test]
+    fn test_forbidden_author() {
+        let forged = |author: String, command: AppCommand| AuthoredCommand::new(author, command);
+        let mut state = AppCore::init();
+        // ホスト以外は参加者を決められない
+        assert_eq!(
+            AppCore::resolve(&state, forged(member_id(1), AppCommand::InitProfile(profiles(2)))),
+            AppResult::Rejected(InvalidCommand::Forbidden)
+        );
+        dispatch(&mut state, AppCommand::InitProfile(profiles(2)));
+        let push = |id: usize| AppCommand::PushPassword(InitPlayer {
+            id: PlayerId(id),
+            password: String::new(),
+            hints: vec![String::new(); 3],
+        });
+        // 他人のあいことばは入力できない
+        assert_eq!(AppCore::resolve(&state, forged(member_id(1), push(0))), AppResult::Rejected(InvalidCommand::Forbidden));
+        assert_eq!(AppCore::resolve(&state, forged("stranger".to_string(), push(0))), AppResult::Rejected(InvalidCommand::Forbidden));
+        assert!(matches!(AppCore::resolve(&state, forged(member_id(0), push(0))), AppResult::PushPassword(_)));
+        dispatch(&mut state, push(0));
+        dispatch(&mut state, push(1));
+        assert_eq!(
+            AppCore::resolve(&state, forged(member_id(0), AppCommand::Guess(PlayerId(1), PlayerId(0)))),
+            AppResult::Rejected(InvalidCommand::Forbidden)
+        );
+        assert!(matches!(
+            AppCore::resolve(&state, forged(member_id(1), AppCommand::Guess(PlayerId(1), PlayerId(0)))),
+            AppResult::Guess(_)
+        ));
+    }
+}
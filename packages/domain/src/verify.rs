@@ -0,0 +1,320 @@
+use std::{cell::{Cell, RefCell}, rc::Rc};
+
+use exprocess::{client::{Record, RecordSync, Repository}, core::ExprocessCore};
+
+use crate::state::{AppCore, AppResult, AppState};
+
+/// 記録された結果が、手元でresolveし直した結果と一致しなかった
+#[derive(Debug,Clone,PartialEq)]
+pub struct ReplayMismatch {
+    pub record_id: String
+}
+
+/// 内側のRepositoryとVerifiedRepositoryで共有する、確かめた結果
+#[derive(Clone,Default)]
+pub struct VerifyLink {
+    trusted_start: Rc<Cell<bool>>,
+    halted: Rc<Cell<bool>>
+}
+
+impl VerifyLink {
+    /// 内側のRepositoryが、以前に最初から辿って確かめたスナップショットから同期を始めた。
+    /// 最初のレコードを渡す前に呼ぶ
+    pub fn start_at_verified_snapshot(&self) {
+        self.trusted_start.set(true);
+    }
+
+    /// 一致しないレコードが見つかった。内側のRepositoryは、それ以降のレコードを反映も追記もしない
+    pub fn is_halted(&self) -> bool {
+        self.halted.get()
+    }
+}
+
+/// 同期したレコードを手元でresolveし直して、結果が改ざんされていないか確かめるRepository。
+/// 一致しないレコードが届いたら、それ以降は反映しない。
+/// 内側のRepositoryが確かめ済みのスナップショットから始めたとVerifyLinkで伝えたときだけ、
+/// 最初に届いたそのスナップショットを、resolveし直さずに信用する。
+/// 部屋の最初から続いている最初の形式のレコードも、resolveし直せないので信用する
+pub struct VerifiedRepository<Repo> {
+    inner: Repo,
+    on_verified_snapshot: Box<dyn FnMut(&AppState)>,
+    link: VerifyLink
+}
+
+impl <Repo> VerifiedRepository<Repo> {
+    pub fn new(inner: Repo) -> Self {
+        Self { inner, on_verified_snapshot: Box::new(|_| {}), link: VerifyLink::default() }
+    }
+
+    /// 内側のRepositoryに渡したものと同じVerifyLinkで、確かめた結果を伝える
+    pub fn link(mut self, link: VerifyLink) -> Self {
+        self.link = link;
+        self
+    }
+
+    /// 途中のスナップショットが、resolveし直した状態と一致したとき
+    pub fn on_verified_snapshot(mut self, callback: Box<dyn FnMut(&AppState)>) -> Self {
+        self.on_verified_snapshot = callback;
+        self
+    }
+}
+
+/// 先頭から順にresolveし直して、一致しなかった最初のレコードの位置を返す
+fn find_mismatch(state: &mut AppState, started: &mut bool, legacy: &mut bool, trusted_start: bool, records: &[RecordSync<AppCore>], on_verified_snapshot: &mut dyn FnMut(&AppState)) -> Option<usize> {
+    records.iter().position(|record| {
+        if !std::mem::replace(started, true) && trusted_start {
+            if let AppResult::Snapshot(_) = record.result {
+                *legacy = false;
+                AppCore::reducer(state, record.result.clone());
+                return false
+            }
+        }
+        if !record.command.is_replayable() {
+            // 今の形式のレコードより後に書かれたものは、古いクライアントのものではない
+            if !*legacy {
+                return true
+            }
+            AppCore::reducer(state, record.result.clone());
+            return false
+        }
+        *legacy = false;
+        let expected = AppCore::resolve(state, record.command.clone());
+        if &expected != record.result {
+            return true
+        }
+        if let AppResult::Snapshot(snapshot) = &expected {
+            on_verified_snapshot(snapshot);
+        }
+        AppCore::reducer(state, expected);
+        false
+    })
+}
+
+impl <Repo: Repository<AppCore,Err>,Err: From<ReplayMismatch> + 'static> Repository<AppCore,Err> for VerifiedRepository<Repo> {
+    fn push(&mut self, record: Record<AppCore>, on_error: Box<dyn FnOnce(Err)>) {
+        self.inner.push(record, on_error)
+    }
+
+    fn sync(&mut self, mut listener: Box<dyn FnMut(Vec<RecordSync<AppCore>>)>, on_error: Box<dyn FnMut(Err)>) {
+        let on_error = Rc::new(RefCell::new(on_error));
+        let on_mismatch = on_error.clone();
+        let mut on_verified_snapshot = std::mem::replace(&mut self.on_verified_snapshot, Box::new(|_| {}));
+        let mut state = Some(AppCore::init());
+        let mut started = false;
+        let mut legacy = true;
+        let link = self.link.clone();
+        self.inner.sync(
+            Box::new(move |mut records: Vec<RecordSync<AppCore>>| {
+                let replica = match state.as_mut() {
+                    Some(replica) => replica,
+                    None => return,
+                };
+                match find_mismatch(replica, &mut started, &mut legacy, link.trusted_start.get(), &records, on_verified_snapshot.as_mut()) {
+                    None => listener(records),
+                    Some(index) => {
+                        let record_id = records[index].id.to_string();
+                        records.truncate(index);
+                        if !records.is_empty() {
+                            listener(records);
+                        }
+                        state = None;
+                        link.halted.set(true);
+                        on_mismatch.borrow_mut()(ReplayMismatch { record_id }.into());
+                    }
+                }
+            }),
+            Box::new(move |err| on_error.borrow_mut()(err)),
+        )
+    }
+
+    fn unsync(&mut self) {
+        self.inner.unsync()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use exprocess::{client::{Record, Repository, Runner}, core::ExprocessCore};
+    use crate::{
+        fixture::{authored, input, profiles, replay, setup},
+        memory::{MemoryLog, MemoryRepository},
+        migration::StoredRecord,
+        model::PlayerId,
+        state::{AppCommand, AppCore, AppResult, AppState},
+    };
+
+    use super::{ReplayMismatch, VerifiedRepository, VerifyLink};
+
+    #[test]
+    fn test_reject_forged_result() {
+        let log = MemoryLog::<AppCore>::new();
+        let state = Rc::new(RefCell::new(AppState::Blank));
+        let mismatches = Rc::new(RefCell::new(Vec::new()));
+        let listener_state = state.clone();
+        let listener_mismatches = mismatches.clone();
+        let link = VerifyLink::default();
+        let mut runner: Runner<AppCore, ReplayMismatch> = Runner::start(
+            VerifiedRepository::new(MemoryRepository::new(&log)).link(link.clone()),
+            Box::new(move |_, app: &AppState| {
+                listener_state.replace(app.clone());
+            }),
+            Box::new(move |mismatch| listener_mismatches.borrow_mut().push(mismatch)),
+        );
+        runner.dispatch(authored(0, AppCommand::InitProfile(profiles(2))));
+        runner.dispatch(authored(0, AppCommand::PushPassword(input(0))));
+        assert!(matches!(&*state.borrow(), AppState::StandbyPassword(_, inputs, _) if inputs.len() == 1));
+
+        // 正しく配られた盤面の、自分のターゲットを書き換えて送りつける
+        let mut forged = match AppCore::resolve(&state.borrow(), authored(1, AppCommand::PushPassword(input(1)))) {
+            AppResult::InitBoard(board) => board,
+            result => panic!("{:?}", result),
+        };
+        let target = forged.players[&PlayerId(0)].target.clone();
+        forged.players.get_mut(&PlayerId(1)).unwrap().target = target;
+        let mut cheater: MemoryRepository<AppCore> = MemoryRepository::new(&log);
+        Repository::<AppCore, ReplayMismatch>::push(
+            &mut cheater,
+            Record {
+                id: "forged".to_string(),
+                command: authored(1, AppCommand::PushPassword(input(1))),
+                result: AppResult::InitBoard(forged),
+            },
+            Box::new(|_| panic!()),
+        );
+        assert_eq!(*mismatches.borrow(), vec![ReplayMismatch { record_id: "forged".to_string() }]);
+        assert!(link.is_halted());
+        assert!(matches!(&*state.borrow(), AppState::StandbyPassword(_, _, _)));
+        // 以降のレコードは反映しない
+        runner.dispatch(authored(0, AppCommand::Guess(PlayerId(0), PlayerId(1))));
+        assert_eq!(log.len(), 4);
+        assert!(matches!(&*state.borrow(), AppState::StandbyPassword(_, _, _)));
+    }
+
+    #[test]
+    fn test_accept_honest_results() {
+        let log = MemoryLog::<AppCore>::new();
+        let state = Rc::new(RefCell::new(AppState::Blank));
+        let listener_state = state.clone();
+        let mut runners: Vec<Runner<AppCore, ReplayMismatch>> = (0..2)
+            .map(|_| {
+                let listener_state = listener_state.clone();
+                Runner::start(
+                    VerifiedRepository::new(MemoryRepository::new(&log)),
+                    Box::new(move |_, app: &AppState| {
+                        listener_state.replace(app.clone());
+                    }),
+                    Box::new(|mismatch| panic!("{:?}", mismatch)),
+                )
+            })
+            .collect();
+        runners[0].dispatch(authored(0, AppCommand::InitProfile(profiles(2))));
+        for (n, runner) in runners.iter_mut().enumerate() {
+            runner.dispatch(authored(n, AppCommand::PushPassword(input(n))));
+        }
+        runners[1].dispatch(authored(1, AppCommand::Guess(PlayerId(1), PlayerId(0))));
+        assert!(matches!(&*state.borrow(), AppState::Board(board, _) if board.guesses.len() == 1));
+    }
+
+    #[test]
+    fn test_report_verified_snapshot() {
+        let log = MemoryLog::<AppCore>::new();
+        let verified = Rc::new(RefCell::new(Vec::new()));
+        let on_verified = verified.clone();
+        let mut runner: Runner<AppCore, ReplayMismatch> = Runner::start(
+            VerifiedRepository::new(MemoryRepository::new(&log))
+                .on_verified_snapshot(Box::new(move |state: &AppState| on_verified.borrow_mut().push(state.clone()))),
+            Box::new(|_, _: &AppState| {}),
+            Box::new(|mismatch| panic!("{:?}", mismatch)),
+        );
+        runner.dispatch(authored(0, AppCommand::InitProfile(profiles(2))));
+        runner.dispatch(authored(0, AppCommand::TakeSnapshot));
+        assert!(matches!(verified.borrow().as_slice(), [AppState::StandbyPassword(_, _, _)]));
+
+        // 最初から辿っていないスナップショットは、確かめたことにならない。
+        // 内側が確かめ済みだと言わなければ、先頭のスナップショットも信用しない
+        let verified = Rc::new(RefCell::new(0));
+        let on_verified = verified.clone();
+        let mismatches = Rc::new(RefCell::new(Vec::new()));
+        let on_mismatch = mismatches.clone();
+        let forged = MemoryLog::<AppCore>::new();
+        Repository::<AppCore, ReplayMismatch>::push(
+            &mut MemoryRepository::new(&forged),
+            Record {
+                id: "forged".to_string(),
+                command: authored(0, AppCommand::TakeSnapshot),
+                result: AppResult::Snapshot(Box::new(AppState::Blank)),
+            },
+            Box::new(|_| panic!()),
+        );
+        let _runner: Runner<AppCore, ReplayMismatch> = Runner::start(
+            VerifiedRepository::new(MemoryRepository::new(&forged))
+                .on_verified_snapshot(Box::new(move |_: &AppState| *on_verified.borrow_mut() += 1)),
+            Box::new(|_, _: &AppState| {}),
+            Box::new(move |mismatch| on_mismatch.borrow_mut().push(mismatch)),
+        );
+        assert_eq!(*verified.borrow(), 0);
+        assert_eq!(*mismatches.borrow(), vec![ReplayMismatch { record_id: "forged".to_string() }]);
+    }
+
+    #[test]
+    fn test_start_from_snapshot() {
+        // 別のログで進めた状態をスナップショットにする
+        let base = replay(setup(2));
+        let log = MemoryLog::<AppCore>::new();
+        let mut writer: MemoryRepository<AppCore> = MemoryRepository::new(&log);
+        Repository::<AppCore, ReplayMismatch>::push(
+            &mut writer,
+            Record {
+                id: "snapshot".to_string(),
+                command: authored(0, AppCommand::TakeSnapshot),
+                result: AppResult::Snapshot(Box::new(base.clone())),
+            },
+            Box::new(|_| panic!()),
+        );
+        let state = Rc::new(RefCell::new(AppState::Blank));
+        let listener_state = state.clone();
+        // 内側が、確かめ済みのスナップショットから始めたと伝える
+        let link = VerifyLink::default();
+        link.start_at_verified_snapshot();
+        let mut runner: Runner<AppCore, ReplayMismatch> = Runner::start(
+            VerifiedRepository::new(MemoryRepository::new(&log)).link(link),
+            Box::new(move |_, app: &AppState| {
+                listener_state.replace(app.clone());
+            }),
+            Box::new(|mismatch| panic!("{:?}", mismatch)),
+        );
+        assert_eq!(*state.borrow(), base);
+        runner.dispatch(authored(0, AppCommand::Guess(PlayerId(0), PlayerId(1))));
+        assert!(matches!(&*state.borrow(), AppState::Board(board, _) if board.guesses.len() == 1));
+    }
+
+    #[test]
+    fn test_trust_first_version_records() {
+        let log = MemoryLog::<AppCore>::new();
+        let mut writer: MemoryRepository<AppCore> = MemoryRepository::new(&log);
+        let records: Vec<StoredRecord> = serde_json::from_str(include_str!("../golden/v1.json")).unwrap();
+        let records: Vec<_> = records.into_iter().map(|record| record.upgrade().unwrap()).collect();
+        for (n, (command, result)) in records.iter().cloned().enumerate() {
+            Repository::<AppCore, ReplayMismatch>::push(&mut writer, Record { id: format!("v1-{}", n), command, result }, Box::new(|_| panic!()));
+        }
+        let state = Rc::new(RefCell::new(AppState::Blank));
+        let mismatches = Rc::new(RefCell::new(Vec::new()));
+        let (listener_state, listener_mismatches) = (state.clone(), mismatches.clone());
+        let mut runner: Runner<AppCore, ReplayMismatch> = Runner::start(
+            VerifiedRepository::new(MemoryRepository::new(&log)),
+            Box::new(move |_, app: &AppState| {
+                listener_state.replace(app.clone());
+            }),
+            Box::new(move |mismatch| listener_mismatches.borrow_mut().push(mismatch)),
+        );
+        // 送り主も無く配り直せない最初の形式は、部屋の最初から続いている間だけ信用する
+        assert!(matches!(&*state.borrow(), AppState::Board(_, _)));
+        runner.dispatch(authored(0, AppCommand::TakeSnapshot));
+        assert!(mismatches.borrow().is_empty());
+        let (command, result) = records[0].clone();
+        Repository::<AppCore, ReplayMismatch>::push(&mut writer, Record { id: "forged".to_string(), command, result }, Box::new(|_| panic!()));
+        assert_eq!(*mismatches.borrow(), vec![ReplayMismatch { record_id: "forged".to_string() }]);
+    }
+}
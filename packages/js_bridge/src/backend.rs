@@ -0,0 +1,396 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use mytil::{Cleaner, FnOnceCleanable};
+use serde_json::{json, Value};
+
+use crate::{
+    create_room, fetch_records, get_verified_snapshot, get_your_id, push_record, set_verified_snapshot, sync_record_update, sync_room,
+    BridgeError, RecordBodyIO, RecordPushIO, Room,
+};
+
+/// 部屋の作成と購読、自分のidの置き場所。Firestoreが無くてもコンテナを動かせるように差し替える
+pub trait RoomBackend {
+    /// 部屋ができたらcallbackにそのidを渡す。返すのも同じid
+    fn create_room(&self, compact_records: bool, callback: Box<dyn FnOnce(String)>, on_error: Box<dyn FnOnce(BridgeError)>) -> String;
+    /// 部屋が無ければNoneを渡す
+    fn sync_room(&self, room_id: &str, callback: Box<dyn FnMut(Option<Room>)>, on_error: Box<dyn FnMut(BridgeError)>) -> Cleaner<FnOnceCleanable>;
+    /// 初めて開いた部屋では新しく作る
+    fn your_id(&self, room_id: &str) -> String;
+    /// 部屋のレコードの置き場所。AppRepositoryはこれを通して読み書きする
+    fn records(&self, room_id: &str) -> Rc<dyn RecordStore>;
+}
+
+/// 1つの部屋のレコードの読み書き。JSONはjs/record.tsのtoRecordObjの形の配列
+pub trait RecordStore {
+    /// 他の人が先に同じseq_noで追記していればBridgeError::Conflict
+    fn push(&self, record: RecordPushIO, on_error: Box<dyn FnOnce(BridgeError)>);
+    /// 最新のスナップショットから、届いたレコードを渡し続ける
+    fn sync(&self, callback: Box<dyn FnMut(String)>, on_error: Box<dyn FnMut(BridgeError)>) -> Cleaner<FnOnceCleanable>;
+    /// from_seq_no以降のレコードを、syncと同じ形で取得する
+    fn fetch(&self, from_seq_no: usize, callback: Box<dyn FnOnce(String)>, on_error: Box<dyn FnOnce(BridgeError)>);
+    /// 最初から辿って確かめたスナップショットの状態のchecksum
+    fn verified_snapshot(&self) -> Option<String>;
+    fn set_verified_snapshot(&self, checksum: &str);
+}
+
+/// js_bridgeを通してFirestoreを使う
+pub struct FirestoreBackend;
+
+impl RoomBackend for FirestoreBackend {
+    fn create_room(&self, compact_records: bool, callback: Box<dyn FnOnce(String)>, on_error: Box<dyn FnOnce(BridgeError)>) -> String {
+        create_room(compact_records, callback, on_error)
+    }
+
+    fn sync_room(&self, room_id: &str, callback: Box<dyn FnMut(Option<Room>)>, on_error: Box<dyn FnMut(BridgeError)>) -> Cleaner<FnOnceCleanable> {
+        let mut cleaner = sync_room(room_id, callback, on_error);
+        FnOnceCleanable::new(move || cleaner.clean()).into()
+    }
+
+    fn your_id(&self, room_id: &str) -> String {
+        get_your_id(room_id)
+    }
+
+    fn records(&self, room_id: &str) -> Rc<dyn RecordStore> {
+        Rc::new(FirestoreRecords { room_id: room_id.to_string() })
+    }
+}
+
+struct FirestoreRecords {
+    room_id: String,
+}
+
+impl RecordStore for FirestoreRecords {
+    fn push(&self, record: RecordPushIO, on_error: Box<dyn FnOnce(BridgeError)>) {
+        push_record(self.room_id.as_str(), record, on_error)
+    }
+
+    fn sync(&self, callback: Box<dyn FnMut(String)>, on_error: Box<dyn FnMut(BridgeError)>) -> Cleaner<FnOnceCleanable> {
+        let mut cleaner = sync_record_update(self.room_id.as_str(), callback, on_error);
+        FnOnceCleanable::new(move || cleaner.clean()).into()
+    }
+
+    fn fetch(&self, from_seq_no: usize, callback: Box<dyn FnOnce(String)>, on_error: Box<dyn FnOnce(BridgeError)>) {
+        fetch_records(self.room_id.as_str(), from_seq_no, callback, on_error)
+    }
+
+    fn verified_snapshot(&self) -> Option<String> {
+        get_verified_snapshot(self.room_id.as_str())
+    }
+
+    fn set_verified_snapshot(&self, checksum: &str) {
+        set_verified_snapshot(self.room_id.as_str(), checksum)
+    }
+}
+
+type RoomListener = Box<dyn FnMut(Option<Room>)>;
+
+#[derive(Default)]
+struct Memory {
+    rooms: BTreeMap<String, Room>,
+    your_ids: BTreeMap<String, String>,
+    listeners: Vec<(usize, String, RoomListener)>,
+    /// 購読を外すときのid
+    counter: usize,
+    records: BTreeMap<String, RoomRecords>,
+    subscribers: Vec<RecordSubscriber>,
+    verified_snapshots: BTreeMap<String, String>,
+    delivering: bool,
+}
+
+#[derive(Default)]
+struct RoomRecords {
+    /// 書き込まれた順のseq_noとレコード
+    records: Vec<(usize, Value)>,
+    latest_snapshot: usize,
+}
+
+struct RecordSubscriber {
+    id: usize,
+    room_id: String,
+    /// 配信済みのレコード数
+    cursor: usize,
+    /// 配信中は取り出されている
+    callback: Option<Box<dyn FnMut(String)>>,
+}
+
+/// js/record.tsのtoRecordObjと同じ形にする。commandとresultがJSONでなければBridgeError::Malformed
+fn record_json(record: &RecordPushIO) -> Result<Value, BridgeError> {
+    Ok(match record.body {
+        RecordBodyIO::Json { command, result } => json!({
+            "id": record.id,
+            "seq_no": record.seq_no,
+            "checksum": record.checksum,
+            "version": record.version,
+            "command": {
+                "author": record.author,
+                "at": record.at,
+                "command": serde_json::from_str::<Value>(command).map_err(|_| BridgeError::Malformed)?
+            },
+            "result": serde_json::from_str::<Value>(result).map_err(|_| BridgeError::Malformed)?
+        }),
+        RecordBodyIO::Compact { payload } => json!({
+            "id": record.id,
+            "seq_no": record.seq_no,
+            "checksum": record.checksum,
+            "payload": payload
+        }),
+    })
+}
+
+/// テストとショーケース用に、手元だけで部屋を持つ。callbackはすぐに呼ばれる
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    memory: Rc<RefCell<Memory>>,
+}
+
+impl MemoryBackend {
+    fn notify(&self, room_id: &str) {
+        let room = self.memory.borrow().rooms.get(room_id).cloned();
+        // 呼ばれたcallbackの中から購読し直せるように、借用を外してから呼ぶ
+        let mut listeners: Vec<_> = {
+            let mut memory = self.memory.borrow_mut();
+            let (matched, rest) = std::mem::take(&mut memory.listeners).into_iter().partition(|(_, id, _)| id == room_id);
+            memory.listeners = rest;
+            matched
+        };
+        for (_, _, listener) in listeners.iter_mut() {
+            listener(room.clone());
+        }
+        self.memory.borrow_mut().listeners.extend(listeners);
+    }
+
+    /// 未配信のレコードを購読しているcallbackに届ける。
+    /// callbackの中で追記された場合も、同じループの中で順番に届ける
+    fn deliver(&self) {
+        if std::mem::replace(&mut self.memory.borrow_mut().delivering, true) {
+            return;
+        }
+        loop {
+            let next = {
+                let mut memory = self.memory.borrow_mut();
+                let Memory { records, subscribers, .. } = &mut *memory;
+                subscribers.iter_mut().find_map(|subscriber| {
+                    let room = records.get(subscriber.room_id.as_str())?;
+                    if subscriber.cursor >= room.records.len() || subscriber.callback.is_none() {
+                        return None;
+                    }
+                    let json = Value::Array(room.records[subscriber.cursor..].iter().map(|(_, record)| record.clone()).collect());
+                    subscriber.cursor = room.records.len();
+                    Some((subscriber.id, subscriber.callback.take().expect("checked above"), json.to_string()))
+                })
+            };
+            let (id, mut callback, json) = match next {
+                Some(next) => next,
+                None => break,
+            };
+            callback(json);
+            let mut memory = self.memory.borrow_mut();
+            if let Some(subscriber) = memory.subscribers.iter_mut().find(|subscriber| subscriber.id == id) {
+                subscriber.callback = Some(callback);
+            }
+        }
+        self.memory.borrow_mut().delivering = false;
+    }
+}
+
+impl RoomBackend for MemoryBackend {
+    fn create_room(&self, compact_records: bool, callback: Box<dyn FnOnce(String)>, _on_error: Box<dyn FnOnce(BridgeError)>) -> String {
+        let room_id = {
+            let mut memory = self.memory.borrow_mut();
+            let room_id = format!("room-{}", memory.rooms.len() + 1);
+            memory.rooms.insert(room_id.clone(), Room { compact_records });
+            room_id
+        };
+        self.notify(room_id.as_str());
+        callback(room_id.clone());
+        room_id
+    }
+
+    fn sync_room(&self, room_id: &str, mut callback: Box<dyn FnMut(Option<Room>)>, _on_error: Box<dyn FnMut(BridgeError)>) -> Cleaner<FnOnceCleanable> {
+        let room = self.memory.borrow().rooms.get(room_id).cloned();
+        callback(room);
+        let id = {
+            let mut memory = self.memory.borrow_mut();
+            memory.counter += 1;
+            let id = memory.counter;
+            memory.listeners.push((id, room_id.to_string(), callback));
+            id
+        };
+        let memory = self.memory.clone();
+        FnOnceCleanable::new(move || memory.borrow_mut().listeners.retain(|(listener, _, _)| *listener != id)).into()
+    }
+
+    fn your_id(&self, room_id: &str) -> String {
+        let mut memory = self.memory.borrow_mut();
+        if let Some(your_id) = memory.your_ids.get(room_id) {
+            return your_id.clone();
+        }
+        let your_id = format!("member-{}", memory.your_ids.len() + 1);
+        memory.your_ids.insert(room_id.to_string(), your_id.clone());
+        your_id
+    }
+
+    fn records(&self, room_id: &str) -> Rc<dyn RecordStore> {
+        Rc::new(MemoryRecords { backend: self.clone(), room_id: room_id.to_string() })
+    }
+}
+
+struct MemoryRecords {
+    backend: MemoryBackend,
+    room_id: String,
+}
+
+impl RecordStore for MemoryRecords {
+    fn push(&self, record: RecordPushIO, on_error: Box<dyn FnOnce(BridgeError)>) {
+        let json = match record_json(&record) {
+            Ok(json) => json,
+            Err(err) => return on_error(err),
+        };
+        {
+            let mut memory = self.backend.memory.borrow_mut();
+            let room = memory.records.entry(self.room_id.clone()).or_default();
+            if room.records.iter().any(|(seq_no, _)| *seq_no == record.seq_no) {
+                drop(memory);
+                return on_error(BridgeError::Conflict);
+            }
+            room.records.push((record.seq_no, json));
+            if record.snapshot {
+                room.latest_snapshot = record.seq_no;
+            }
+        }
+        self.backend.deliver();
+    }
+
+    fn sync(&self, callback: Box<dyn FnMut(String)>, _on_error: Box<dyn FnMut(BridgeError)>) -> Cleaner<FnOnceCleanable> {
+        let id = {
+            let mut memory = self.backend.memory.borrow_mut();
+            memory.counter += 1;
+            let id = memory.counter;
+            // 最新のスナップショットより前は配らない
+            let cursor = memory.records.get(self.room_id.as_str()).map_or(0, |room| {
+                room.records.iter().position(|(seq_no, _)| *seq_no >= room.latest_snapshot).unwrap_or(room.records.len())
+            });
+            memory.subscribers.push(RecordSubscriber { id, room_id: self.room_id.clone(), cursor, callback: Some(callback) });
+            id
+        };
+        self.backend.deliver();
+        let memory = self.backend.memory.clone();
+        FnOnceCleanable::new(move || memory.borrow_mut().subscribers.retain(|subscriber| subscriber.id != id)).into()
+    }
+
+    fn fetch(&self, from_seq_no: usize, callback: Box<dyn FnOnce(String)>, _on_error: Box<dyn FnOnce(BridgeError)>) {
+        let json = {
+            let memory = self.backend.memory.borrow();
+            let mut records: Vec<_> = memory
+                .records
+                .get(self.room_id.as_str())
+                .map_or(Vec::new(), |room| room.records.iter().filter(|(seq_no, _)| *seq_no >= from_seq_no).collect());
+            records.sort_by_key(|(seq_no, _)| *seq_no);
+            Value::Array(records.into_iter().map(|(_, record)| record.clone()).collect()).to_string()
+        };
+        callback(json);
+    }
+
+    fn verified_snapshot(&self) -> Option<String> {
+        self.backend.memory.borrow().verified_snapshots.get(self.room_id.as_str()).cloned()
+    }
+
+    fn set_verified_snapshot(&self, checksum: &str) {
+        self.backend.memory.borrow_mut().verified_snapshots.insert(self.room_id.clone(), checksum.to_string());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use serde_json::Value;
+
+    use super::{MemoryBackend, RoomBackend};
+    use crate::{BridgeError, RecordBodyIO, RecordPushIO, Room};
+
+    fn record(seq_no: usize, snapshot: bool) -> RecordPushIO<'static> {
+        RecordPushIO {
+            id: "record",
+            author: "member-1",
+            at: None,
+            body: RecordBodyIO::Json { command: r#"{"type":"Leave"}"#, result: r#"{"type":"Left","value":"member-1"}"# },
+            seq_no,
+            checksum: "checksum",
+            version: 2,
+            snapshot,
+        }
+    }
+
+    fn seq_nos(json: &str) -> Vec<u64> {
+        let records: Vec<Value> = serde_json::from_str(json).unwrap();
+        records.iter().map(|record| record["seq_no"].as_u64().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_sync_created_room() {
+        let backend = MemoryBackend::default();
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let listener = received.clone();
+        let mut cleaner = backend.sync_room("room-1", Box::new(move |room| listener.borrow_mut().push(room)), Box::new(|_| ()));
+        let created = Rc::new(RefCell::new(None));
+        let callback = created.clone();
+        let room_id = backend.create_room(true, Box::new(move |id| *callback.borrow_mut() = Some(id)), Box::new(|_| ()));
+        assert_eq!(room_id, "room-1");
+        assert_eq!(created.borrow().as_deref(), Some("room-1"));
+        assert_eq!(*received.borrow(), vec![None, Some(Room { compact_records: true })]);
+        cleaner.clean();
+        backend.create_room(false, Box::new(|_| ()), Box::new(|_| ()));
+        assert_eq!(received.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_your_id_is_kept_per_room() {
+        let backend = MemoryBackend::default();
+        let your_id = backend.your_id("room-1");
+        assert_eq!(backend.your_id("room-1"), your_id);
+        assert_ne!(backend.your_id("room-2"), your_id);
+    }
+
+    #[test]
+    fn test_push_and_sync_records() {
+        let backend = MemoryBackend::default();
+        let records = backend.records("room-1");
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let listener = received.clone();
+        let mut cleaner = records.sync(Box::new(move |json| listener.borrow_mut().push(seq_nos(json.as_str()))), Box::new(|_| ()));
+        records.push(record(0, false), Box::new(|err| panic!("{}", err)));
+        let conflict = Rc::new(RefCell::new(None));
+        let on_conflict = conflict.clone();
+        records.push(record(0, false), Box::new(move |err| *on_conflict.borrow_mut() = Some(err)));
+        assert_eq!(*conflict.borrow(), Some(BridgeError::Conflict));
+        records.push(record(1, true), Box::new(|err| panic!("{}", err)));
+        assert_eq!(*received.borrow(), vec![vec![0], vec![1]]);
+        // 後から購読すると最新のスナップショットから届く
+        let late = Rc::new(RefCell::new(Vec::new()));
+        let listener = late.clone();
+        let mut late_cleaner = records.sync(Box::new(move |json| listener.borrow_mut().push(seq_nos(json.as_str()))), Box::new(|_| ()));
+        assert_eq!(*late.borrow(), vec![vec![1]]);
+        cleaner.clean();
+        late_cleaner.clean();
+        let fetched = Rc::new(RefCell::new(Vec::new()));
+        let callback = fetched.clone();
+        records.fetch(0, Box::new(move |json| *callback.borrow_mut() = seq_nos(json.as_str())), Box::new(|_| ()));
+        assert_eq!(*fetched.borrow(), vec![0, 1]);
+        // 部屋ごとに分かれている
+        assert_eq!(records.verified_snapshot(), None);
+        records.set_verified_snapshot("checksum");
+        assert_eq!(records.verified_snapshot().as_deref(), Some("checksum"));
+        assert_eq!(backend.records("room-2").verified_snapshot(), None);
+    }
+
+    #[test]
+    fn test_reject_malformed_record() {
+        let records = MemoryBackend::default().records("room-1");
+        let err = Rc::new(RefCell::new(None));
+        let on_error = err.clone();
+        let malformed = RecordPushIO { body: RecordBodyIO::Json { command: "{", result: "null" }, ..record(0, false) };
+        records.push(malformed, Box::new(move |e| *on_error.borrow_mut() = Some(e)));
+        assert_eq!(*err.borrow(), Some(BridgeError::Malformed));
+    }
+}
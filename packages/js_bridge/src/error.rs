@@ -0,0 +1,71 @@
+use std::fmt::{self, Display, Formatter};
+
+use wasm_bindgen::prelude::*;
+
+/// js/error.tsのBridgeErrorKindで渡される、JS側で起きた失敗
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum BridgeError {
+    PermissionDenied,
+    NotFound,
+    Network,
+    /// 他の人が先に同じseq_noで追記した
+    Conflict,
+    Quota,
+    /// JSから受け取ったJSONが想定した形ではない
+    Malformed,
+    Unexpected
+}
+
+impl BridgeError {
+    /// 知らない種類はUnexpectedにする
+    pub fn from_kind(kind: &str) -> Self {
+        match kind {
+            "PERMISSION_DENIED" => BridgeError::PermissionDenied,
+            "NOT_FOUND" => BridgeError::NotFound,
+            "NETWORK" => BridgeError::Network,
+            "CONFLICT" => BridgeError::Conflict,
+            "QUOTA" => BridgeError::Quota,
+            "MALFORMED" => BridgeError::Malformed,
+            _ => BridgeError::Unexpected,
+        }
+    }
+}
+
+impl Display for BridgeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BridgeError::PermissionDenied => write!(f, "permission denied"),
+            BridgeError::NotFound => write!(f, "not found"),
+            BridgeError::Network => write!(f, "network error"),
+            BridgeError::Conflict => write!(f, "conflict"),
+            BridgeError::Quota => write!(f, "quota exceeded"),
+            BridgeError::Malformed => write!(f, "malformed data"),
+            BridgeError::Unexpected => write!(f, "unexpected error"),
+        }
+    }
+}
+
+/// 一度だけ呼ばれるonErrorをJSに渡す
+pub(crate) fn once_on_error<OE: FnOnce(BridgeError) + 'static>(on_error: OE) -> JsValue {
+    Closure::once_into_js(move |kind: String| on_error(BridgeError::from_kind(kind.as_str())))
+}
+
+/// 購読中に何度でも呼ばれるonErrorをJSに渡す
+pub(crate) fn on_error<OE: FnMut(BridgeError) + 'static>(mut on_error: OE) -> JsValue {
+    let on_error: Box<dyn FnMut(String)> = Box::new(move |kind: String| on_error(BridgeError::from_kind(kind.as_str())));
+    Closure::wrap(on_error).into_js_value()
+}
+
+#[cfg(test)]
+mod test {
+    use super::BridgeError;
+
+    #[test]
+    fn test_from_kind() {
+        assert_eq!(BridgeError::from_kind("PERMISSION_DENIED"), BridgeError::PermissionDenied);
+        assert_eq!(BridgeError::from_kind("CONFLICT"), BridgeError::Conflict);
+        assert_eq!(BridgeError::from_kind("QUOTA"), BridgeError::Quota);
+        assert_eq!(BridgeError::from_kind("MALFORMED"), BridgeError::Malformed);
+        assert_eq!(BridgeError::from_kind("permission-denied"), BridgeError::Unexpected);
+    }
+}
@@ -1,4 +1,6 @@
 
+use std::{cell::RefCell, rc::Rc};
+
 use mytil::{Cleanable, Cleaner};
 use wasm_bindgen::prelude::*;
 use js_sys::Function;
@@ -6,34 +8,61 @@ use serde::{Deserialize, Serialize};
 use serde_json::{self};
 use names::{Generator, Name};
 
-#[wasm_bindgen()]
-extern "C" {
-    #[wasm_bindgen(js_name = "registerMember",js_namespace = ["window","_wasm_js_bridge"])]
-    fn register_member_bridge(room_id: &str,name: &str,on_error: JsValue);
-
-    #[wasm_bindgen(js_name = "syncMember",js_namespace = ["window","_wasm_js_bridge"])]
-    fn sync_member_bridge(room_id: &str,callback: JsValue,on_error: JsValue) -> Function;
+pub mod backend;
+mod error;
 
-    #[wasm_bindgen(js_name = "fetchMembers",js_namespace = ["window","_wasm_js_bridge"])]
-    fn fetch_members_bridge(room_id: &str,callback: JsValue,on_error: JsValue);
+pub use error::BridgeError;
+use error::{on_error as on_error_js, once_on_error};
 
+#[wasm_bindgen()]
+extern "C" {
     #[wasm_bindgen(js_name = "createRoom",js_namespace = ["window","_wasm_js_bridge"])]
-    fn create_room_bridge(room_id: &str,hostName:&str,callback: JsValue,on_error: JsValue);
+    fn create_room_bridge(room_id: &str,encoding: &str,callback: JsValue,on_error: JsValue);
 
     #[wasm_bindgen(js_name = "syncRoom",js_namespace = ["window","_wasm_js_bridge"])]
     fn sync_room_bridge(room_id: &str,callback: JsValue,on_error: JsValue) -> Function;
 
-    #[wasm_bindgen(js_name = "startRoom",js_namespace = ["window","_wasm_js_bridge"])]
-    fn start_room_bridge(room_id: &str,on_error: JsValue);
-
     #[wasm_bindgen(js_name = "pushRecord",js_namespace = ["window","_wasm_js_bridge"])]
-    fn push_record_bridge(room_id: &str,record_id: &str, command: &str, result: &str,on_error: JsValue);
+    fn push_record_bridge(room_id: &str,record_id: &str, author: &str, at: Option<f64>, command: Option<&str>, result: Option<&str>, payload: Option<&str>,seq_no: usize,checksum: &str,version: u32,snapshot: bool,on_error: JsValue);
 
     #[wasm_bindgen(js_name = "syncRecordUpdate",js_namespace = ["window","_wasm_js_bridge"])]
     fn sync_record_update_bridge(room_id: &str,callback: JsValue,on_error: JsValue) -> Function;
 
+    #[wasm_bindgen(js_name = "fetchRecords",js_namespace = ["window","_wasm_js_bridge"])]
+    fn fetch_records_bridge(room_id: &str,from_seq_no: usize,callback: JsValue,on_error: JsValue);
+
     #[wasm_bindgen(js_name = "getYourId",js_namespace = ["window","_wasm_js_bridge"])]
-    fn get_your_id_bridge(room_id: &str) -> Option<String>;
+    fn get_your_id_bridge(room_id: &str) -> String;
+
+    #[wasm_bindgen(js_name = "getNotebook",js_namespace = ["window","_wasm_js_bridge"])]
+    fn get_notebook_bridge(room_id: &str) -> Option<String>;
+
+    #[wasm_bindgen(js_name = "setNotebook",js_namespace = ["window","_wasm_js_bridge"])]
+    fn set_notebook_bridge(room_id: &str,json: &str);
+
+    #[wasm_bindgen(js_name = "getVerifiedSnapshot",js_namespace = ["window","_wasm_js_bridge"])]
+    fn get_verified_snapshot_bridge(room_id: &str) -> Option<String>;
+
+    #[wasm_bindgen(js_name = "setVerifiedSnapshot",js_namespace = ["window","_wasm_js_bridge"])]
+    fn set_verified_snapshot_bridge(room_id: &str,checksum: &str);
+
+    #[wasm_bindgen(js_name = "shareSvg",js_namespace = ["window","_wasm_js_bridge"])]
+    fn share_svg_bridge(file_name: &str,svg: &str,on_error: JsValue);
+
+    #[wasm_bindgen(js_name = "getLocalRecords",js_namespace = ["window","_wasm_js_bridge"])]
+    fn get_local_records_bridge(room_id: &str) -> Option<String>;
+
+    #[wasm_bindgen(js_name = "setLocalRecords",js_namespace = ["window","_wasm_js_bridge"])]
+    fn set_local_records_bridge(room_id: &str,json: &str);
+
+    #[wasm_bindgen(js_name = "clearLocalRecords",js_namespace = ["window","_wasm_js_bridge"])]
+    fn clear_local_records_bridge(room_id: &str);
+
+    #[wasm_bindgen(js_name = "syncServerRecord",js_namespace = ["window","_wasm_js_bridge"])]
+    fn sync_server_record_bridge(url: &str,room_id: &str,callback: JsValue,on_error: JsValue) -> Function;
+
+    #[wasm_bindgen(js_name = "pushServerCommand",js_namespace = ["window","_wasm_js_bridge"])]
+    fn push_server_command_bridge(room_id: &str,record_id: &str,command: &str,on_error: JsValue);
 }
 
 
@@ -57,130 +86,200 @@ pub type JSFunctionCleaner = Cleaner<CleanableJSFunction>;
 
 
 
-pub fn register_member<OE: FnOnce() + 'static>(room_id: &str,name: &str,on_error: OE) {
-    register_member_bridge(room_id,name, Closure::once_into_js(Box::new(on_error)))
+pub fn sync_record_update<F: FnMut(String) + 'static,E: FnMut(BridgeError) + 'static>(room_id: &str, callback: F,on_error: E) -> JSFunctionCleaner {
+    let callback: Box<dyn FnMut(String)> = Box::new(callback);
+    let callback = Closure::wrap( callback).into_js_value();
+    CleanableJSFunction::from(sync_record_update_bridge(room_id,callback,on_error_js(on_error))).into()
 }
 
-pub fn sync_record_update<F: FnMut(String) + 'static,E: FnMut() + 'static>(room_id: &str, callback: F,on_error: E) -> JSFunctionCleaner {
+pub fn sync_server_record<F: FnMut(String) + 'static,E: FnMut(BridgeError) + 'static>(url: &str,room_id: &str, callback: F,on_error: E) -> JSFunctionCleaner {
     let callback: Box<dyn FnMut(String)> = Box::new(callback);
     let callback = Closure::wrap( callback).into_js_value();
-    let on_error: Box<dyn FnMut()>  = Box::new(on_error);
-    let on_error = Closure::wrap(on_error).into_js_value();
-    CleanableJSFunction::from(sync_record_update_bridge(room_id,callback,on_error)).into()
+    CleanableJSFunction::from(sync_server_record_bridge(url,room_id,callback,on_error_js(on_error))).into()
 }
 
-#[derive(Serialize, Deserialize)]
-struct MemberJSON<'a> {
-    pub name: &'a str,
-    pub id: &'a str,
-    pub you: bool
+/// 先にsync_server_recordで接続しておくこと
+pub fn push_server_command<OE: FnOnce(BridgeError) + 'static>(room_id: &str,record_id: &str,command: &str,on_error: OE) {
+    push_server_command_bridge(room_id,record_id,command,once_on_error(on_error))
 }
 
-
-fn json_to_members<'a>(json:&'a str) -> Vec<Member<'a>> {
-    let members_json : Vec<MemberJSON> = serde_json::from_str(json).expect("JSON Parse Error");
-    members_json
-            .iter()
-            .map(|member| Member {id:member.id,name: member.name, you: member.you})
-            .collect::<Vec<Member>>()
-}
-
-pub fn sync_members<CB: FnMut(Vec<Member>) + 'static,OE: FnMut() + 'static>(room_id: &str,mut callback:CB,on_error: OE) -> JSFunctionCleaner {
-    let json_callback : Box<dyn FnMut(String)>= Box::new(
-        move |json:String| callback(json_to_members(&json))
-    );
-    let on_error : Box<dyn FnMut()> = Box::new(on_error);
-    CleanableJSFunction::from(sync_member_bridge(
-        room_id, 
-        Closure::wrap(json_callback).into_js_value(),
-        Closure::wrap(on_error).into_js_value(),
-    )).into()
-}
-
-pub fn fetch_members<CB: FnOnce(Vec<Member>) + 'static,OE: FnOnce() + 'static>(room_id: &str,callback:CB,on_error: OE) {
-    fetch_members_bridge( 
-        room_id, 
-        Closure::once_into_js(move |json:String| callback(json_to_members(json.as_str()))),
-        Closure::once_into_js (on_error),
+/// from_seq_no以降のレコードを、sync_record_updateと同じ形で取得する
+pub fn fetch_records<CB: FnOnce(String) + 'static,OE: FnOnce(BridgeError) + 'static>(room_id: &str,from_seq_no: usize,callback: CB,on_error: OE) {
+    fetch_records_bridge(
+        room_id,
+        from_seq_no,
+        Closure::once_into_js(callback),
+        once_on_error(on_error)
     )
 }
 
-pub struct Member<'a> {
-    pub id: &'a str,
-    pub name: &'a str,
-    pub you: bool
-}
-
-pub fn create_room<CB: FnOnce() + 'static,OE: FnOnce() + 'static>(hostname: &str,callback : CB,on_error: OE) -> String {
+/// compact_recordsなら、その部屋のレコードを圧縮して保存する
+/// 部屋ができたらcallbackにそのidを渡す
+pub fn create_room<CB: FnOnce(String) + 'static,OE: FnOnce(BridgeError) + 'static>(compact_records: bool,callback : CB,on_error: OE) -> String {
     let mut generator = Generator::with_naming(Name::Numbered);
     let room_id = generator.next().unwrap();
     create_room_bridge(
         room_id.as_str(),
-        hostname,
+        if compact_records { "compact" } else { "json" },
         Closure::once_into_js(callback),
-        Closure::once_into_js(on_error)
+        once_on_error(on_error)
     );
     room_id
 }
 
-pub enum Phase {
-    Meeting,
-    Started
+/// 参加や開始はレコードで決まるので、部屋そのものには保存形式しかない
+#[derive(Debug,Clone,PartialEq)]
+pub struct Room {
+    /// レコードを圧縮して保存する部屋か
+    pub compact_records: bool
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RoomJSON {
+    pub encoding: EncodingJSON
+}
 
-pub struct Room {
-    pub phase: Phase,
-    pub is_host: bool  
+#[derive(Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodingJSON {
+    Json,
+    Compact
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct RoomJSON<'a> {
-    pub phase: &'a str,
-    pub is_host: bool  
+/// 知らない形の部屋はBridgeError::Malformedにする
+fn decode_room(json: &str) -> Result<Room,BridgeError> {
+    let room: RoomJSON = serde_json::from_str(json).map_err(|_| BridgeError::Malformed)?;
+    Ok(Room { compact_records: room.encoding == EncodingJSON::Compact })
 }
 
-pub fn sync_room<CB: FnMut(Option<Room>) + 'static, OE: FnMut() + 'static>(room_id: &str,mut callback:CB,on_error: OE) -> Cleaner<CleanableJSFunction> {
+pub fn sync_room<CB: FnMut(Option<Room>) + 'static, OE: FnMut(BridgeError) + 'static>(room_id: &str,mut callback:CB,on_error: OE) -> Cleaner<CleanableJSFunction> {
+    let on_error = Rc::new(RefCell::new(on_error));
+    let on_decode_error = on_error.clone();
     let callback: Box<dyn FnMut(Option<String>)> = Box::new(move |room| {
-        let room = room.map(|room| -> Room {
-            let room: RoomJSON = serde_json::from_str(room.as_str()).expect("JSON Parse Error");
-            Room { 
-                phase: match room.phase {
-                    "MEETING" => Phase::Meeting,
-                    "STARTED" => Phase::Started,
-                    invalid => panic!("Invalid Value {}",invalid)
-                }, 
-                is_host: room.is_host 
-            }
-        });
-        callback(room);
+        match room.map(|room| decode_room(room.as_str())).transpose() {
+            Ok(room) => callback(room),
+            Err(err) => (on_decode_error.borrow_mut())(err),
+        }
     });
-    let on_error: Box<dyn FnMut()> = Box::new(on_error);
     let callback = Closure::wrap(callback).into_js_value();
-    let on_error = Closure::wrap(on_error).into_js_value();
-    CleanableJSFunction::from(sync_room_bridge(room_id,callback,on_error)).into()
-}
-
-pub fn start_room<OE: FnOnce() + 'static>(room_id: &str,on_error: OE) {
-    start_room_bridge(room_id,Closure::once_into_js(on_error));
+    CleanableJSFunction::from(sync_room_bridge(room_id,callback,on_error_js(move |err| (on_error.borrow_mut())(err)))).into()
 }
 
 pub struct RecordPushIO<'a> {
     pub id: &'a str,
-    pub command: &'a str,
-    pub result: &'a str
+    /// 送ったメンバーのid
+    pub author: &'a str,
+    /// 送った端末の時刻（ミリ秒）
+    pub at: Option<u64>,
+    pub body: RecordBodyIO<'a>,
+    /// 追記できるのは、このseq_noがまだ使われていないときだけ
+    pub seq_no: usize,
+    /// このレコードまで反映した状態のchecksum
+    pub checksum: &'a str,
+    /// commandとresultの形式のバージョン
+    pub version: u32,
+    /// 同期をここから始められるスナップショットか
+    pub snapshot: bool
+}
+
+/// 部屋の保存形式ごとのレコードの中身
+pub enum RecordBodyIO<'a> {
+    Json {
+        command: &'a str,
+        result: &'a str
+    },
+    /// commandとresultをまとめて圧縮したもののbase64
+    Compact {
+        payload: &'a str
+    }
 }
 
-pub fn push_record<OE: FnOnce() + 'static>(room_id: &str,record: RecordPushIO,on_error: OE) {
+/// 他の人が先に同じseq_noで追記していればBridgeError::Conflict
+pub fn push_record<OE: FnOnce(BridgeError) + 'static>(room_id: &str,record: RecordPushIO,on_error: OE) {
     push_record_bridge(
         room_id,
         record.id,
-        record.command,
-        record.result,
-        Closure::once_into_js(on_error)
+        record.author,
+        record.at.map(|at| at as f64),
+        match record.body {
+            RecordBodyIO::Json { command, .. } => Some(command),
+            RecordBodyIO::Compact { .. } => None
+        },
+        match record.body {
+            RecordBodyIO::Json { result, .. } => Some(result),
+            RecordBodyIO::Compact { .. } => None
+        },
+        match record.body {
+            RecordBodyIO::Json { .. } => None,
+            RecordBodyIO::Compact { payload } => Some(payload)
+        },
+        record.seq_no,
+        record.checksum,
+        record.version,
+        record.snapshot,
+        once_on_error(on_error)
     )
 }
 
-pub fn get_your_id(room_id: &str) -> Option<String> {
+/// 初めて開いた部屋では新しく作る
+pub fn get_your_id(room_id: &str) -> String {
     get_your_id_bridge(room_id)
-}
\ No newline at end of file
+}
+
+pub fn get_notebook(room_id: &str) -> Option<String> {
+    get_notebook_bridge(room_id)
+}
+
+pub fn set_notebook(room_id: &str,json: &str) {
+    set_notebook_bridge(room_id,json)
+}
+
+/// 最初から辿って確かめたスナップショットの状態のchecksum
+pub fn get_verified_snapshot(room_id: &str) -> Option<String> {
+    get_verified_snapshot_bridge(room_id)
+}
+
+pub fn set_verified_snapshot(room_id: &str,checksum: &str) {
+    set_verified_snapshot_bridge(room_id,checksum)
+}
+
+pub fn share_svg<OE: FnOnce(BridgeError) + 'static>(file_name: &str,svg: &str,on_error: OE) {
+    share_svg_bridge(file_name,svg,once_on_error(on_error))
+}
+
+pub fn get_local_records(room_id: &str) -> Option<String> {
+    get_local_records_bridge(room_id)
+}
+
+pub fn set_local_records(room_id: &str,json: &str) {
+    set_local_records_bridge(room_id,json)
+}
+
+pub fn clear_local_records(room_id: &str) {
+    clear_local_records_bridge(room_id)
+}
+#[cfg(test)]
+mod test {
+    use crate::{decode_room, BridgeError, Room};
+
+    #[test]
+    fn test_decode_room() {
+        assert_eq!(decode_room(r#"{"encoding":"compact"}"#), Ok(Room { compact_records: true }));
+        assert_eq!(decode_room(r#"{"encoding":"json","phase":"STARTED"}"#), Ok(Room { compact_records: false }));
+    }
+
+    #[test]
+    fn test_reject_malformed_room() {
+        for json in [
+            "",
+            "null",
+            "{",
+            "{}",
+            r#"{"encoding":"cbor"}"#,
+            r#"{"encoding":1}"#,
+            r#"{"encoding":null}"#,
+        ] {
+            assert_eq!(decode_room(json), Err(BridgeError::Malformed), "{}", json);
+        }
+    }
+}
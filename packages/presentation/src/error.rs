@@ -1,7 +1,10 @@
-use yew::{Html, html};
+use yew::{Callback, Html, html};
 
-pub fn error() -> Html{
+pub fn error(message: &str, action: &str, on_action: &Callback<()>) -> Html{
     html! {
-        <div>{"Error"}</div>
+        <div class="section">
+            <p class="mb-4">{message}</p>
+            <button onclick=on_action.reform(|_| ()) class="button is-link">{action}</button>
+        </div>
     }
-}
\ No newline at end of file
+}
@@ -3,7 +3,10 @@ use yew::prelude::*;
 use crate::main_vis::main_vis_container;
 use crate::title::{title, title_description};
 use crate::open_form::OpenForm;
-pub fn home(on_submit: &Callback<String>) -> Html {
+pub use crate::open_form::OpenRoom;
+
+/// compact_recordsは圧縮するかどうかの最初の選択
+pub fn home(on_submit: &Callback<OpenRoom>, compact_records: bool) -> Html {
     html! {
         <>
             {main_vis_container(
@@ -15,7 +18,8 @@ pub fn home(on_submit: &Callback<String>) -> Html {
                 }
             )}
             <div class="section">
-                <OpenForm on_submit=on_submit />
+                <OpenForm on_submit=on_submit compact_records=compact_records />
+                <a href="/local">{"一台の端末で遊ぶ"}</a>
             </div>
         </>
     }
@@ -11,4 +11,6 @@ mod how_to_use;
 pub mod error;
 pub mod not_found;
 pub mod sleep;
-pub mod playing;
\ No newline at end of file
+pub mod playing;
+pub mod pass_and_play;
+pub mod spectator;
\ No newline at end of file
@@ -8,7 +8,7 @@ use crate::join_form::JoinForm;
 
 pub enum GuestForm {
     Joinable { join: Callback<String> },
-    Joined,
+    Joined { leave: Callback<()> },
     Loading,
 }
 
@@ -32,8 +32,11 @@ pub fn guest_form(form: &GuestForm) -> Html {
                 <JoinForm on_submit=join />
             }
         },
-        GuestForm::Joined => html! {
-            <h3 class="title is-4">{"ホストが開始するのを待っています。"}</h3>
+        GuestForm::Joined { leave } => html! {
+            <>
+                <h3 class="title is-4">{"ホストが開始するのを待っています。"}</h3>
+                <button onclick=leave.reform(|_| ()) class="button">{"抜ける"}</button>
+            </>
         },
         GuestForm::Loading => loading(),
     }
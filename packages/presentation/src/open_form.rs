@@ -1,19 +1,32 @@
 use yew::prelude::*;
 pub struct OpenForm {
     value: String,
+    compact_records: bool,
     link: ComponentLink<Self>,
     props: Props,
 }
 
-type OnSubmit = Callback<String>;
+/// 作る部屋の設定
+#[derive(Clone, PartialEq, Debug)]
+pub struct OpenRoom {
+    pub name: String,
+    /// レコードを圧縮して保存するか
+    pub compact_records: bool,
+}
+
+type OnSubmit = Callback<OpenRoom>;
 
 #[derive(Clone, PartialEq, Properties)]
 pub struct Props {
     pub on_submit: OnSubmit,
+    /// 圧縮するかどうかの最初の選択
+    #[prop_or_default]
+    pub compact_records: bool,
 }
 
 pub enum Msg {
     Change(String),
+    ToggleCompact,
     Submit,
 }
 
@@ -24,6 +37,7 @@ impl Component for OpenForm {
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
             value: "ホスト".into(),
+            compact_records: props.compact_records,
             props: props,
             link,
         }
@@ -32,8 +46,12 @@ impl Component for OpenForm {
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             Msg::Change(value) => self.value = value,
+            Msg::ToggleCompact => self.compact_records = !self.compact_records,
             Msg::Submit => {
-                self.props.on_submit.emit(self.value.clone());
+                self.props.on_submit.emit(OpenRoom {
+                    name: self.value.clone(),
+                    compact_records: self.compact_records,
+                });
             }
         }
         true
@@ -49,12 +67,19 @@ impl Component for OpenForm {
             _ => panic!("Invalid Type"),
         });
         let onclick = self.link.callback(|_| Msg::Submit);
+        let ontoggle = self.link.callback(|_| Msg::ToggleCompact);
         let value = self.value.clone();
         html! {
             <div class="columns">
                 <div class="column is-one-fifth">
                     <input type="text" class="input" placeholder="あなたの名前" value=value minlength=1 onchange=onchange/>
                 </div>
+                <div class="column is-narrow">
+                    <label class="checkbox">
+                        <input type="checkbox" checked=self.compact_records onclick=ontoggle/>
+                        {"記録を圧縮して保存する"}
+                    </label>
+                </div>
                 <div class="column">
                     <button class="button is-link" onclick=onclick>{"部屋を作成"}</button>
                 </div>
@@ -0,0 +1,94 @@
+use yew::prelude::*;
+
+use crate::members::{members_view, Member};
+
+/// 一台の端末で遊ぶ参加者を集める
+pub struct LocalSetupForm {
+    value: String,
+    names: Vec<String>,
+    link: ComponentLink<Self>,
+    props: Props,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub on_submit: Callback<Vec<String>>,
+}
+
+pub enum Msg {
+    Change(String),
+    Add,
+    Submit,
+}
+
+impl Component for LocalSetupForm {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            value: String::new(),
+            names: Vec::new(),
+            link,
+            props,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Change(value) => self.value = value,
+            Msg::Add => {
+                if !self.value.is_empty() {
+                    self.names.push(std::mem::take(&mut self.value));
+                }
+            }
+            Msg::Submit => self.props.on_submit.emit(self.names.clone()),
+        }
+        true
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        let oninput = self.link.callback(|input: InputData| Msg::Change(input.value));
+        let add = self.link.callback(|_| Msg::Add);
+        let submit = self.link.callback(|_| Msg::Submit);
+        let members = self
+            .names
+            .iter()
+            .map(|name| Member {
+                name: name.clone(),
+                you: false,
+            })
+            .collect();
+        let disabled = self.names.len() < 2;
+        html! {
+            <div class="section">
+                <h3 class="title is-4">{"一台で遊ぶ人を登録しましょう。"}</h3>
+                <div class="columns">
+                    <div class="column is-one-fifth">
+                        <input type="text" class="input" placeholder="名前" value=self.value.clone() oninput=oninput/>
+                    </div>
+                    <div class="column">
+                        <button class="button" onclick=add>{"追加"}</button>
+                    </div>
+                </div>
+                {members_view(&members)}
+                <button class="button is-link" onclick=submit disabled=disabled>{"はじめる"}</button>
+            </div>
+        }
+    }
+}
+
+/// 次の人に端末を渡すまで画面を隠す
+pub fn hand_over(name: &str, ready: &Callback<()>) -> Html {
+    let onclick = ready.reform(|_| ());
+    html! {
+        <div class="section has-text-centered">
+            <h3 class="title is-4">{format!("{}さんに端末を渡してください。", name)}</h3>
+            <button class="button is-link" onclick=onclick>{format!("{}です", name)}</button>
+        </div>
+    }
+}
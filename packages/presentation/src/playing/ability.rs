@@ -0,0 +1,26 @@
+use yew::prelude::*;
+
+pub struct AbilityView {
+    pub role: String,
+    pub description: String,
+    /// 使い終わったら空
+    pub actions: Vec<(String, Callback<()>)>,
+}
+
+pub fn ability(view: &AbilityView) -> Html {
+    let actions = view.actions.iter().map(|(label, action)| {
+        let onclick = action.reform(|_| ());
+        html! {
+            <button onclick=onclick class="button is-link is-small">{label.as_str()}</button>
+        }
+    });
+    html! {
+        <div class="box">
+            <p><strong>{view.role.as_str()}</strong></p>
+            <p>{view.description.as_str()}</p>
+            <div class="buttons">
+                {for actions}
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,29 @@
+use yew::prelude::*;
+
+pub struct GuessView {
+    /// 推理できない理由などの状況
+    pub status: String,
+    /// 直前の自分の推理の結果
+    pub last: Option<String>,
+    /// 推理できないときは空
+    pub suspects: Vec<(String, Callback<()>)>,
+}
+
+pub fn guess_form(view: &GuessView) -> Html {
+    let suspects = view.suspects.iter().map(|(name, guess)| {
+        let onclick = guess.reform(|_| ());
+        html! {
+            <button onclick=onclick class="button is-danger is-small">{format!("{}がターゲット", name)}</button>
+        }
+    });
+    html! {
+        <div class="box">
+            <p><strong>{"推理する"}</strong></p>
+            <p>{view.status.as_str()}</p>
+            {view.last.as_ref().map_or(html! {}, |last| html! { <p>{last.as_str()}</p> })}
+            <div class="buttons">
+                {for suspects}
+            </div>
+        </div>
+    }
+}
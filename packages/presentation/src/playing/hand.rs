@@ -13,7 +13,9 @@ pub type HandHints = Vec<(String,HintType)>;
 
 #[derive(Debug,Clone)]
 pub enum HintType {
-    None,Target
+    None,Target,
+    /// 能力で知った、ターゲットのヒント
+    Revealed
 }
 
 impl Component for Hand {
@@ -1,2 +1,9 @@
 pub mod password_form;
-pub mod hand;
\ No newline at end of file
+pub mod hand;
+pub mod trade;
+pub mod notebook;
+pub mod reveal;
+pub mod result_card;
+pub mod roles;
+pub mod ability;
+pub mod guess;
\ No newline at end of file
@@ -0,0 +1,61 @@
+use yew::prelude::*;
+
+pub struct NotebookView {
+    /// 列に並べる手元のヒント
+    pub hints: Vec<String>,
+    pub rows: Vec<NotebookRow>,
+}
+
+pub struct NotebookRow {
+    pub name: String,
+    /// hintsと同じ順
+    pub cells: Vec<(Option<NoteMark>, Callback<()>)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum NoteMark {
+    Confirmed,
+    Suspect,
+    RuledOut,
+}
+
+pub fn notebook(view: &NotebookView) -> Html {
+    let hints = view.hints.iter().map(|hint| html! { <th>{hint.as_str()}</th> });
+    let rows = view.rows.iter().map(|row| {
+        let cells = row.cells.iter().map(|(mark, toggle)| {
+            let onclick = toggle.reform(|_| ());
+            let (label, class) = match mark {
+                Some(NoteMark::Confirmed) => ("◎", "button is-small is-success"),
+                Some(NoteMark::Suspect) => ("△", "button is-small is-warning"),
+                Some(NoteMark::RuledOut) => ("×", "button is-small is-light"),
+                None => ("-", "button is-small"),
+            };
+            html! {
+                <td>
+                    <button onclick=onclick class=class>{label}</button>
+                </td>
+            }
+        });
+        html! {
+            <tr>
+                <th>{row.name.as_str()}</th>
+                {for cells}
+            </tr>
+        }
+    });
+    html! {
+        <div class="table-container">
+            <table class="table is-narrow">
+                <thead>
+                    <tr>
+                        <th>{"メモ"}</th>
+                        {for hints}
+                    </tr>
+                </thead>
+                <tbody>
+                    {for rows}
+                </tbody>
+            </table>
+        </div>
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt::Write;
+
+use yew::prelude::*;
+
+pub struct ResultCard {
+    pub room: String,
+    /// 順位順
+    pub standings: Vec<ResultStanding>,
+    pub play_time_secs: Option<u64>,
+}
+
+pub struct ResultStanding {
+    pub name: String,
+    pub score: i32,
+    pub password: Option<String>,
+}
+
+const WIDTH: usize = 600;
+const HEADER: usize = 120;
+const ROW: usize = 40;
+
+fn escape(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut acc, c| {
+        match c {
+            '&' => acc.push_str("&amp;"),
+            '<' => acc.push_str("&lt;"),
+            '>' => acc.push_str("&gt;"),
+            '"' => acc.push_str("&quot;"),
+            '\'' => acc.push_str("&apos;"),
+            c => acc.push(c),
+        }
+        acc
+    })
+}
+
+fn play_time(secs: u64) -> String {
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// 外部リソースに依存しない単体のSVGを生成する
+pub fn render_result_card(card: &ResultCard) -> String {
+    let height = HEADER + ROW * card.standings.len() + ROW;
+    let mut svg = String::new();
+    write!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">"##,
+        w = WIDTH,
+        h = height
+    )
+    .unwrap();
+    write!(svg, r##"<rect width="{}" height="{}" rx="16" fill="#ffffff" stroke="#3273dc" stroke-width="4"/>"##, WIDTH, height).unwrap();
+    write!(svg, r##"<text x="32" y="56" font-size="28" font-weight="bold" fill="#363636">Find Meee!!!</text>"##).unwrap();
+    write!(svg, r##"<text x="32" y="92" font-size="18" fill="#4a4a4a">{}</text>"##, escape(card.room.as_str())).unwrap();
+    if let Some(secs) = card.play_time_secs {
+        write!(
+            svg,
+            r##"<text x="{}" y="92" font-size="18" fill="#4a4a4a" text-anchor="end">プレイ時間 {}</text>"##,
+            WIDTH - 32,
+            play_time(secs)
+        )
+        .unwrap();
+    }
+    for (index, standing) in card.standings.iter().enumerate() {
+        let y = HEADER + ROW * index + ROW / 2;
+        write!(svg, r##"<text x="32" y="{}" font-size="20" fill="#3273dc">{}.</text>"##, y, index + 1).unwrap();
+        write!(svg, r##"<text x="72" y="{}" font-size="20" fill="#363636">{}</text>"##, y, escape(standing.name.as_str())).unwrap();
+        if let Some(password) = &standing.password {
+            write!(svg, r##"<text x="300" y="{}" font-size="16" fill="#7a7a7a">あいことば: {}</text>"##, y, escape(password.as_str())).unwrap();
+        }
+        write!(
+            svg,
+            r##"<text x="{}" y="{}" font-size="20" fill="#363636" text-anchor="end">{}pt</text>"##,
+            WIDTH - 32,
+            y,
+            standing.score
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// imgのsrcやダウンロードリンクに使う
+pub fn to_data_url(svg: &str) -> String {
+    let mut url = String::from("data:image/svg+xml;charset=utf-8,");
+    for byte in svg.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'=' | b':' | b'/' | b',' => {
+                url.push(byte as char)
+            }
+            byte => write!(url, "%{:02X}", byte).unwrap(),
+        }
+    }
+    url
+}
+
+pub fn result_card(card: &ResultCard, share: &Callback<String>) -> Html {
+    let svg = render_result_card(card);
+    let url = to_data_url(svg.as_str());
+    let onclick = share.reform(move |_| svg.clone());
+    html! {
+        <section class="section">
+            <img src=url.clone() alt="result" style="max-width: 600px; width: 100%"/>
+            <div class="buttons mt-2">
+                <a class="button" href=url download="find-meee-result.svg">{"画像を保存"}</a>
+                <button class="button is-link" onclick=onclick>{"シェアする"}</button>
+            </div>
+        </section>
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{play_time, render_result_card, to_data_url, ResultCard, ResultStanding};
+
+    fn card() -> ResultCard {
+        ResultCard {
+            room: "tall-room-42".to_string(),
+            standings: vec![
+                ResultStanding {
+                    name: "<aaaa>".to_string(),
+                    score: 2,
+                    password: Some("りんご".to_string()),
+                },
+                ResultStanding {
+                    name: "iii".to_string(),
+                    score: -1,
+                    password: None,
+                },
+            ],
+            play_time_secs: Some(754),
+        }
+    }
+
+    #[test]
+    fn test_render_result_card() {
+        let svg = render_result_card(&card());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>"));
+        assert!(svg.contains("tall-room-42"));
+        assert!(svg.contains("12:34"));
+        // 名前はエスケープされる
+        assert!(svg.contains("&lt;aaaa&gt;"));
+        assert!(!svg.contains("<aaaa>"));
+        // 公開されたあいことばだけ載る
+        assert_eq!(svg.matches("あいことば").count(), 1);
+        assert!(svg.find("&lt;aaaa&gt;").unwrap() < svg.find("iii").unwrap());
+    }
+
+    #[test]
+    fn test_render_without_play_time() {
+        let svg = render_result_card(&ResultCard {
+            play_time_secs: None,
+            ..card()
+        });
+        assert!(!svg.contains("プレイ時間"));
+    }
+
+    #[test]
+    fn test_play_time() {
+        assert_eq!(play_time(59), "0:59");
+        assert_eq!(play_time(3601), "60:01");
+    }
+
+    #[test]
+    fn test_to_data_url() {
+        assert_eq!(
+            to_data_url("<a href=\"#\">あ</a>"),
+            "data:image/svg+xml;charset=utf-8,%3Ca%20href=%22%23%22%3E%E3%81%82%3C/a%3E"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use std::f64::consts::PI;
+
+use yew::prelude::*;
+
+pub struct RevealView {
+    pub players: Vec<String>,
+    pub arrows: Vec<RevealArrowView>,
+}
+
+pub struct RevealArrowView {
+    /// playersのindex
+    pub hunter: usize,
+    pub target: usize,
+    pub hint: String,
+    /// 何回目の推理で見つけたか
+    pub found_at: Option<usize>,
+}
+
+const SIZE: f64 = 480.0;
+const RADIUS: f64 = 180.0;
+const NODE_RADIUS: f64 = 28.0;
+
+fn position(index: usize, len: usize) -> (f64, f64) {
+    let angle = 2.0 * PI * index as f64 / len as f64 - PI / 2.0;
+    (SIZE / 2.0 + RADIUS * angle.cos(), SIZE / 2.0 + RADIUS * angle.sin())
+}
+
+fn arrow_view(arrow: &RevealArrowView, len: usize) -> Html {
+    let (x1, y1) = position(arrow.hunter, len);
+    let (x2, y2) = position(arrow.target, len);
+    let distance = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt().max(1.0);
+    // 円に重ならないように端を縮める
+    let (dx, dy) = ((x2 - x1) / distance * NODE_RADIUS, (y2 - y1) / distance * NODE_RADIUS);
+    let (label_x, label_y) = ((x1 + x2) / 2.0, (y1 + y2) / 2.0);
+    let (status, color) = match arrow.found_at {
+        Some(order) => (format!("{}番目に発見", order), "#48c774"),
+        None => ("見つけられず".to_string(), "#b5b5b5"),
+    };
+    html! {
+        <g>
+            <line
+                x1=(x1 + dx).to_string() y1=(y1 + dy).to_string()
+                x2=(x2 - dx).to_string() y2=(y2 - dy).to_string()
+                stroke=color stroke-width="2" marker-end="url(#reveal-arrow)"
+            />
+            <text x=label_x.to_string() y=label_y.to_string() text-anchor="middle" font-size="12">
+                <tspan x=label_x.to_string() dy="-0.3em">{arrow.hint.as_str()}</tspan>
+                <tspan x=label_x.to_string() dy="1.2em" fill=color>{status}</tspan>
+            </text>
+        </g>
+    }
+}
+
+pub fn reveal(view: &RevealView) -> Html {
+    let len = view.players.len();
+    let arrows = view.arrows.iter().map(|arrow| arrow_view(arrow, len));
+    let players = view.players.iter().enumerate().map(|(index, name)| {
+        let (x, y) = position(index, len);
+        html! {
+            <g>
+                <circle cx=x.to_string() cy=y.to_string() r=NODE_RADIUS.to_string() fill="#ffffff" stroke="#3273dc" stroke-width="2"/>
+                <text x=x.to_string() y=y.to_string() text-anchor="middle" dominant-baseline="middle" font-size="12">{name.as_str()}</text>
+            </g>
+        }
+    });
+    let view_box = format!("0 0 {} {}", SIZE, SIZE);
+    html! {
+        <section class="section">
+            <h3 class="title is-4">{"だれがだれを探していたか"}</h3>
+            <svg viewBox=view_box width="100%" style="max-width: 480px">
+                <defs>
+                    <marker id="reveal-arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="6" markerHeight="6" orient="auto">
+                        <path d="M 0 0 L 10 5 L 0 10 z" fill="#4a4a4a"/>
+                    </marker>
+                </defs>
+                {for arrows}
+                {for players}
+            </svg>
+        </section>
+    }
+}
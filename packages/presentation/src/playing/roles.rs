@@ -0,0 +1,37 @@
+use yew::prelude::*;
+
+pub struct RoleOption {
+    pub name: String,
+    pub description: String,
+    pub selected: bool,
+    pub toggle: Callback<()>,
+}
+
+/// ホストが配る役職を選ぶ
+pub fn role_picker(options: &Vec<RoleOption>) -> Html {
+    let options = options.iter().map(|option| {
+        let onclick = option.toggle.reform(|_| ());
+        let class = if option.selected { "button is-link is-small" } else { "button is-small" };
+        html! {
+            <tr>
+                <td>
+                    <button onclick=onclick class=class>{option.name.as_str()}</button>
+                </td>
+                <td>{option.description.as_str()}</td>
+            </tr>
+        }
+    });
+    html! {
+        <table class="table">
+            <thead>
+                <tr>
+                    <th>{"配る役職"}</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+                {for options}
+            </tbody>
+        </table>
+    }
+}
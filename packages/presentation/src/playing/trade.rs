@@ -0,0 +1,155 @@
+use yew::prelude::*;
+
+pub struct TradeOffer {
+    pub from: String,
+    /// 受け取るヒント
+    pub offer: String,
+    /// 渡すヒント
+    pub request: String,
+    pub accept: Callback<()>,
+    pub decline: Callback<()>,
+}
+
+pub fn trade_inbox(offers: &Vec<TradeOffer>) -> Html {
+    if offers.is_empty() {
+        return html! {};
+    }
+    let offers = offers.iter().map(|offer| {
+        let accept = offer.accept.reform(|_| ());
+        let decline = offer.decline.reform(|_| ());
+        html! {
+            <tr>
+                <td>{offer.from.as_str()}</td>
+                <td>{offer.offer.as_str()}</td>
+                <td>{offer.request.as_str()}</td>
+                <td>
+                    <div class="buttons">
+                        <button onclick=accept class="button is-link is-small">{"交換する"}</button>
+                        <button onclick=decline class="button is-small">{"断る"}</button>
+                    </div>
+                </td>
+            </tr>
+        }
+    });
+    html! {
+        <table class="table">
+            <thead>
+                <tr>
+                    <th>{"交換の相手"}</th>
+                    <th>{"もらえるひんと"}</th>
+                    <th>{"渡すひんと"}</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+                {for offers}
+            </tbody>
+        </table>
+    }
+}
+
+#[derive(Clone)]
+pub struct TradePartner {
+    pub name: String,
+    /// 相手が持っているひんとの枚数。中身は見えない
+    pub cards: usize,
+}
+
+/// それぞれpartners、offers、相手のひんとのindex
+#[derive(Clone)]
+pub struct TradeProposal {
+    pub partner: usize,
+    pub offer: usize,
+    pub card: usize,
+}
+
+pub struct ProposeTrade {
+    partner: Option<usize>,
+    offer: Option<usize>,
+    card: Option<usize>,
+    link: ComponentLink<Self>,
+    props: Props,
+}
+
+#[derive(Properties, Clone)]
+pub struct Props {
+    pub partners: Vec<TradePartner>,
+    /// 渡せるひんと
+    pub offers: Vec<String>,
+    pub propose: Callback<TradeProposal>,
+}
+
+pub enum Msg {
+    Partner(usize),
+    Offer(usize),
+    Card(usize),
+    Submit,
+}
+
+impl Component for ProposeTrade {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self { partner: None, offer: None, card: None, link, props }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Partner(partner) => {
+                self.partner = Some(partner);
+                self.card = None;
+            }
+            Msg::Offer(offer) => self.offer = Some(offer),
+            Msg::Card(card) => self.card = Some(card),
+            Msg::Submit => {
+                if let (Some(partner), Some(offer), Some(card)) = (self.partner, self.offer, self.card) {
+                    self.props.propose.emit(TradeProposal { partner, offer, card });
+                    self.partner = None;
+                    self.offer = None;
+                    self.card = None;
+                }
+            }
+        }
+        true
+    }
+
+    /// 盤面が変わると手元のひんとも変わるので選び直してもらう
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        self.partner = None;
+        self.offer = None;
+        self.card = None;
+        true
+    }
+
+    fn view(&self) -> Html {
+        let choices = |labels: Vec<String>, selected: Option<usize>, msg: fn(usize) -> Msg| {
+            let buttons = labels.into_iter().enumerate().map(|(index, label)| {
+                let onclick = self.link.callback(move |_| msg(index));
+                let class = if selected == Some(index) { "button is-link is-small" } else { "button is-small" };
+                html! { <button onclick=onclick class=class>{label}</button> }
+            });
+            html! { <div class="buttons">{for buttons}</div> }
+        };
+        let partners = self.props.partners.iter().map(|partner| partner.name.clone()).collect();
+        let cards = self
+            .partner
+            .and_then(|partner| self.props.partners.get(partner))
+            .map_or(0, |partner| partner.cards);
+        let onclick = self.link.callback(|_| Msg::Submit);
+        let ready = self.partner.is_some() && self.offer.is_some() && self.card.is_some();
+        html! {
+            <div class="box">
+                <p><strong>{"交換を申し込む"}</strong></p>
+                <label class="label">{"交換の相手"}</label>
+                {choices(partners, self.partner, Msg::Partner)}
+                <label class="label">{"渡すひんと"}</label>
+                {choices(self.props.offers.clone(), self.offer, Msg::Offer)}
+                <label class="label">{"もらうひんと"}</label>
+                {choices((1..=cards).map(|n| format!("{}枚目", n)).collect(), self.card, Msg::Card)}
+                <button onclick=onclick class="button is-link" disabled=!ready>{"申し込む"}</button>
+            </div>
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use yew::prelude::*;
+
+use crate::members::{Member, members_view};
+
+/// 始まったあとに部屋へ来た人には、参加者だけを見せる
+pub fn spectator(members: &Vec<Member>) -> Html {
+    html! {
+        <section class="section">
+            <h3 class="title is-4">{"ゲームはもう始まっています。"}</h3>
+            <p class="mb-4">{"参加していないので、終わるまで観戦できます。"}</p>
+            {members_view(members)}
+        </section>
+    }
+}
@@ -0,0 +1,2 @@
+pub mod protocol;
+pub mod room;
\ No newline at end of file
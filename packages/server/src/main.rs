@@ -0,0 +1,131 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env,
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    rc::Rc,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use server::{
+    protocol::{CommandMessage, RecordMessage},
+    room::Room,
+};
+use tungstenite::{
+    accept_hdr,
+    handshake::server::{Callback, ErrorResponse, Request, Response},
+    Error as WsError, Message, WebSocket,
+};
+
+/// 部屋と、その部屋を購読しているクライアントへの送信口
+#[derive(Default)]
+struct Entry {
+    room: Room,
+    subscribers: Vec<Sender<String>>,
+}
+
+type Rooms = Arc<Mutex<HashMap<String, Entry>>>;
+
+fn to_json(records: &[RecordMessage]) -> String {
+    serde_json::to_string(records).expect("records are always serializable")
+}
+
+/// 接続したら今までのレコードを全部送り、以降は増えた分だけを送る
+fn subscribe(rooms: &Rooms, room_id: &str) -> Receiver<String> {
+    let (sender, receiver) = channel();
+    let mut rooms = rooms.lock().expect("poisoned");
+    let entry = rooms.entry(room_id.to_string()).or_default();
+    sender.send(to_json(entry.room.records())).ok();
+    entry.subscribers.push(sender);
+    receiver
+}
+
+fn receive(rooms: &Rooms, room_id: &str, text: &str) {
+    let message: CommandMessage = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(err) => {
+            log::warn!("invalid message in {}: {}", room_id, err);
+            return;
+        }
+    };
+    let mut rooms = rooms.lock().expect("poisoned");
+    let entry = rooms.entry(room_id.to_string()).or_default();
+    if let Some(record) = entry.room.apply(message) {
+        let json = to_json(std::slice::from_ref(record));
+        // 切断済みのクライアントはここで取り除く
+        entry.subscribers.retain(|subscriber| subscriber.send(json.clone()).is_ok());
+    }
+}
+
+fn serve(rooms: &Rooms, socket: &mut WebSocket<TcpStream>, room_id: &str) -> Result<(), Box<WsError>> {
+    let outbox = subscribe(rooms, room_id);
+    // 読み込みで待ち続けないように、定期的に送信待ちを確認する
+    socket.get_ref().set_read_timeout(Some(Duration::from_millis(50))).map_err(WsError::Io)?;
+    loop {
+        for json in outbox.try_iter() {
+            socket.write_message(Message::Text(json))?;
+        }
+        match socket.read_message() {
+            Ok(Message::Text(text)) => receive(rooms, room_id, text.as_str()),
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(WsError::Io(err)) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                socket.write_pending()?;
+            }
+            Err(WsError::ConnectionClosed) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// ハンドシェイクのリクエストから部屋のidを取り出す。ws://host/{room_id}
+struct RoomPath(Rc<RefCell<String>>);
+
+impl Callback for RoomPath {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        self.0.replace(request.uri().path().trim_start_matches('/').to_string());
+        Ok(response)
+    }
+}
+
+fn handle(rooms: Rooms, stream: TcpStream) {
+    let path = Rc::new(RefCell::new(String::new()));
+    let mut socket = match accept_hdr(stream, RoomPath(path.clone())) {
+        Ok(socket) => socket,
+        Err(err) => {
+            log::warn!("handshake failed: {}", err);
+            return;
+        }
+    };
+    let room_id = path.take();
+    let result = if room_id.is_empty() {
+        socket.close(None).map_err(Box::new)
+    } else {
+        serve(&rooms, &mut socket, room_id.as_str())
+    };
+    if let Err(err) = result {
+        log::warn!("connection in {} closed: {}", room_id, err);
+    }
+}
+
+fn main() {
+    let addr = env::args().nth(1).unwrap_or_else(|| "127.0.0.1:8787".to_string());
+    let listener = TcpListener::bind(addr.as_str()).unwrap_or_else(|err| panic!("failed to bind {}: {}", addr, err));
+    println!("listening on ws://{}", addr);
+    let rooms: Rooms = Arc::default();
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let rooms = rooms.clone();
+                thread::spawn(move || handle(rooms, stream));
+            }
+            Err(err) => log::warn!("failed to accept: {}", err),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use domain::state::{AppResult, AuthoredCommand};
+use serde::{Deserialize, Serialize};
+
+/// クライアントから送るのはコマンドだけ。結果はサーバーが決める
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommandMessage {
+    pub id: String,
+    pub command: AuthoredCommand,
+}
+
+/// サーバーからはレコードの配列を送る。Firestoreの同期と同じ形
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordMessage {
+    pub id: String,
+    /// ログの中での位置
+    pub seq_no: usize,
+    /// commandとresultの形式のバージョン
+    pub version: u32,
+    pub command: AuthoredCommand,
+    pub result: AppResult,
+}
@@ -0,0 +1,114 @@
+use domain::{migration::schema_version, state::{AppCore, AppState}};
+use exprocess::core::ExprocessCore;
+
+use crate::protocol::{CommandMessage, RecordMessage};
+
+/// 部屋ごとのレコードのログと、それを畳み込んだ状態
+pub struct Room {
+    state: AppState,
+    records: Vec<RecordMessage>,
+}
+
+impl Default for Room {
+    fn default() -> Self {
+        Self {
+            state: AppCore::init(),
+            records: Vec::new(),
+        }
+    }
+}
+
+impl Room {
+    pub fn records(&self) -> &[RecordMessage] {
+        &self.records
+    }
+
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    /// サーバーの状態でresolveして記録する。
+    /// 再送などで同じidが届いたときは何もしない
+    pub fn apply(&mut self, message: CommandMessage) -> Option<&RecordMessage> {
+        if self.records.iter().any(|record| record.id == message.id) {
+            return None;
+        }
+        let result = AppCore::resolve(&self.state, message.command.clone());
+        AppCore::reducer(&mut self.state, result.clone());
+        self.records.push(RecordMessage {
+            id: message.id,
+            seq_no: self.records.len(),
+            version: schema_version(&message.command),
+            command: message.command,
+            result,
+        });
+        self.records.last()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use domain::{
+        function::InitPlayer,
+        model::PlayerId,
+        profile::{PlayerProfile, Profiles},
+        state::{AppCommand, AppResult, AppState, AuthoredCommand},
+    };
+
+    use crate::protocol::CommandMessage;
+
+    use super::Room;
+
+    /// 操作するプレイヤー本人が送ったことにする
+    fn message(id: &str, command: AppCommand) -> CommandMessage {
+        let author = command.actor().map_or(0, |actor| actor.0).to_string();
+        CommandMessage { id: id.to_string(), command: AuthoredCommand::new(author, command) }
+    }
+
+    #[test]
+    fn test_apply_resolves_on_server() {
+        let mut room = Room::default();
+        let profiles = Profiles {
+            players: (0..2)
+                .map(|n| (n.to_string(), PlayerProfile { id: PlayerId(n), display_name: n.to_string() }))
+                .collect(),
+            host: "0".to_string(),
+        };
+        assert!(matches!(
+            room.apply(message("a", AppCommand::InitProfile(profiles))).map(|record| &record.result),
+            Some(AppResult::InitProfile(_))
+        ));
+        for n in 0..2 {
+            room.apply(message(
+                n.to_string().as_str(),
+                AppCommand::PushPassword(InitPlayer {
+                    id: PlayerId(n),
+                    password: n.to_string(),
+                    hints: (0..3).map(|m| format!("{}-{}", n, m)).collect(),
+                }),
+            ));
+        }
+        assert!(matches!(room.records().last().map(|record| &record.result), Some(AppResult::InitBoard(_))));
+        assert!(matches!(room.state(), AppState::Board(_, _)));
+        // フェーズに合わないコマンドも拒否として記録される
+        assert!(matches!(
+            room.apply(message("b", AppCommand::PushPassword(InitPlayer {
+                id: PlayerId(0),
+                password: String::new(),
+                hints: Vec::new(),
+            })))
+            .map(|record| &record.result),
+            Some(AppResult::Rejected(_))
+        ));
+        assert_eq!(room.records().len(), 4);
+        assert!(room.records().iter().enumerate().all(|(index, record)| record.seq_no == index));
+    }
+
+    #[test]
+    fn test_apply_ignores_duplicated_id() {
+        let mut room = Room::default();
+        assert!(room.apply(message("a", AppCommand::Guess(PlayerId(0), PlayerId(1)))).is_some());
+        assert!(room.apply(message("a", AppCommand::Guess(PlayerId(0), PlayerId(1)))).is_none());
+        assert_eq!(room.records().len(), 1);
+    }
+}
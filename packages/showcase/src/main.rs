@@ -1,6 +1,7 @@
 use cafeteria::yew::{dir, picture, Gallery, GalleryConfig, GalleryModel};
+use js_bridge::backend::{MemoryBackend, RoomBackend};
 use presentation::{
-    home::home,
+    home::{home, OpenRoom},
     meeting::{meeting_guest, GuestForm},
     members::Member,
     sleep::sleep,
@@ -8,13 +9,27 @@ use presentation::{
 use wasm_bindgen::prelude::*;
 use yew::prelude::*;
 use presentation::playing::password_form::PasswordForm;
+use presentation::playing::trade::{trade_inbox, TradeOffer};
+
+thread_local! {
+    /// ショーケースで作った部屋はFirestoreに置かず手元に持つ
+    static BACKEND: MemoryBackend = MemoryBackend::default();
+}
+
+fn create_room() -> Callback<OpenRoom> {
+    Callback::from(|open: OpenRoom| {
+        BACKEND.with(|backend| {
+            backend.create_room(open.compact_records, Box::new(|_| ()), Box::new(|_| ()));
+        })
+    })
+}
 
 pub struct Config;
 
 impl GalleryConfig for Config {
     fn model() -> GalleryModel {
         GalleryModel::new([
-            ("home", picture(|| home(&Callback::noop()))),
+            ("home", picture(|| home(&create_room(), false))),
             (
                 "meeting",
                 dir([
@@ -46,6 +61,15 @@ impl GalleryConfig for Config {
                     <PasswordForm hints_num=3 submit=Callback::noop()/>
                 }
             })),
+            ("trade", picture(|| {
+                trade_inbox(&vec![TradeOffer {
+                    from: "aaaa".to_string(),
+                    offer: "メガネをかけている".to_string(),
+                    request: "背が高い".to_string(),
+                    accept: Callback::noop(),
+                    decline: Callback::noop(),
+                }])
+            })),
             ("sleep", picture(sleep)),
         ])
     }
@@ -0,0 +1,96 @@
+use std::rc::Rc;
+
+use js_bridge::{backend::RoomBackend, clear_local_records};
+use presentation::pass_and_play::{hand_over, LocalSetupForm};
+use yew::prelude::*;
+
+use crate::{containers::main::Main, error::AppError};
+
+const ROOM_ID: &str = "local";
+
+/// 一台の端末を順番に回して遊ぶ
+pub struct LocalGame {
+    state: State,
+    props: Props,
+    link: ComponentLink<Self>,
+}
+
+enum State {
+    Setup,
+    Playing {
+        members: Vec<String>,
+        turn: usize,
+        handing_over: bool,
+    },
+}
+
+pub enum Msg {
+    Start(Vec<String>),
+    EndTurn,
+    Ready,
+}
+
+#[derive(Clone, Properties)]
+pub struct Props {
+    pub backend: Rc<dyn RoomBackend>,
+    pub on_error: Callback<AppError>,
+}
+
+impl Component for LocalGame {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            state: State::Setup,
+            props,
+            link,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match (&mut self.state, msg) {
+            (State::Setup, Msg::Start(members)) => {
+                clear_local_records(ROOM_ID);
+                self.state = State::Playing {
+                    members,
+                    turn: 0,
+                    handing_over: true,
+                };
+            }
+            (State::Playing { members, turn, handing_over }, Msg::EndTurn) => {
+                *turn = (*turn + 1) % members.len();
+                *handing_over = true;
+            }
+            (State::Playing { handing_over, .. }, Msg::Ready) => *handing_over = false,
+            _ => return false,
+        }
+        true
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        match &self.state {
+            State::Setup => html! {
+                <LocalSetupForm on_submit=self.link.callback(Msg::Start)/>
+            },
+            State::Playing { members, turn, handing_over: true } => {
+                hand_over(members[*turn].as_str(), &self.link.callback(|_| Msg::Ready))
+            }
+            // 渡すたびに作り直して、記録から盤面を組み立て直す
+            State::Playing { members, turn, handing_over: false } => html! {
+                <Main
+                    backend=self.props.backend.clone()
+                    room_id=ROOM_ID.to_string()
+                    your_id=turn.to_string()
+                    on_error=self.props.on_error.clone()
+                    local_members=Some(members.clone())
+                    on_dispatch=self.link.callback(|_| Msg::EndTurn)
+                />
+            },
+        }
+    }
+}
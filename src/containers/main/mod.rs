@@ -1,30 +1,60 @@
-use crate::domain::{repository::RepositoryError, start, Runner};
+use std::rc::Rc;
+
+use crate::{domain::{repository::RepositoryError, server_url, start, start_local, start_server, Runner}, error::AppError};
+use exprocess::client::Listener;
 
 use domain::{
-    model::PlayerId,
-    profile::{PlayerProfile, Profiles},
-    state::AppCommand,
+    encoding::RecordEncoding,
+    notebook::{Mark, Notebook},
+    profile::Profiles,
+    state::{AppCommand, AppCore, AppState, AuthoredCommand},
+};
+use js_bridge::{backend::RoomBackend, get_notebook, set_notebook, share_svg};
+use presentation::{
+    loading::loading,
+    meeting::{meeting_guest, meeting_host},
+    spectator::spectator,
+    playing::{
+        hand::Hand,
+        notebook::{notebook, NoteMark, NotebookRow, NotebookView},
+        ability::ability,
+        guess::guess_form,
+        result_card::result_card,
+        reveal::reveal,
+        roles::role_picker,
+        trade::{trade_inbox, ProposeTrade},
+    },
 };
-use js_bridge::fetch_members;
-use presentation::{loading::loading, playing::hand::Hand};
+use webutil::window::now;
 use yew::prelude::*;
 mod model;
-use crate::containers::main::model::{app_state_to_view_state, Msg, ViewState};
+use crate::containers::main::model::{app_state_to_view_state, Msg, NotebookAxes, ViewState};
 use presentation::playing::password_form::PasswordForm;
 
 pub struct Main {
     runner: Runner,
     state: ViewState,
+    notebook: Notebook,
     props: Props,
     link: ComponentLink<Self>,
 }
 
 #[derive(Clone, Properties)]
 pub struct Props {
-    pub is_host: bool,
+    /// 部屋のレコードの置き場所
+    pub backend: Rc<dyn RoomBackend>,
     pub room_id: String,
     pub your_id: String,
-    pub on_error: Callback<()>,
+    pub on_error: Callback<AppError>,
+    /// 一台の端末で遊ぶときの参加者。idは並び順
+    #[prop_or_default]
+    pub local_members: Option<Vec<String>>,
+    /// 自分の操作を送ったとき
+    #[prop_or_default]
+    pub on_dispatch: Callback<()>,
+    /// 部屋のレコードの保存形式
+    #[prop_or_default]
+    pub record_encoding: RecordEncoding,
 }
 
 impl Component for Main {
@@ -34,25 +64,28 @@ impl Component for Main {
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let link_listener = link.clone();
         let link_on_error = props.on_error.clone();
-        let is_host = props.is_host;
         let your_id = props.your_id.clone();
-        let runner = start(
-            props.room_id.clone(),
-            Box::new(move |_, state| {
-                let state = app_state_to_view_state(
-                    &state,
-                    is_host,
-                    your_id.as_str(),
-                    &link_listener.callback(|e| e),
-                );
-                link_listener.send_message(Msg::UpdateState(state))
-            }),
-            Box::new(move |err| match err {
-                RepositoryError::UnExpected => link_on_error.emit(()),
-            }),
-        );
+        let listener: Listener<AppCore, AppState> = Box::new(move |_, state| {
+            let state = app_state_to_view_state(
+                &state,
+                your_id.as_str(),
+                &link_listener.callback(|e| e),
+            );
+            link_listener.send_message(Msg::UpdateState(state))
+        });
+        let on_error: Box<dyn FnMut(RepositoryError)> = Box::new(move |err: RepositoryError| link_on_error.emit(err.into()));
+        let room_id = props.room_id.clone();
+        let runner = match (&props.local_members, server_url()) {
+            (Some(_), _) => start_local(room_id, listener, on_error),
+            (None, Some(url)) => start_server(url, room_id, listener, on_error),
+            (None, None) => start(props.backend.records(room_id.as_str()), props.your_id.clone(), props.record_encoding, listener, on_error),
+        };
+        let notebook = get_notebook(notebook_key(&props).as_str())
+            .and_then(|json| serde_json::from_str(json.as_str()).ok())
+            .unwrap_or_default();
         Main {
             state: ViewState::Blank,
+            notebook,
             runner,
             props,
             link,
@@ -61,37 +94,41 @@ impl Component for Main {
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::UpdateState(state) => {
-                if matches!(state, ViewState::Blank) && self.props.is_host {
-                    let link = self.link.clone();
-                    let on_error = self.props.on_error.clone();
-                    fetch_members(
-                        self.props.room_id.as_str(),
-                        move |members| {
-                            let profiles = Profiles {
-                                players: members
-                                    .into_iter()
-                                    .enumerate()
-                                    .map(|(index, member)| {
-                                        (
-                                            member.id.to_string(),
-                                            PlayerProfile {
-                                                id: PlayerId(index),
-                                                display_name: member.name.to_string(),
-                                            },
-                                        )
-                                    })
-                                    .collect(),
-                            };
-                            let command = AppCommand::InitProfile(profiles);
-                            link.send_message(Msg::PushCommand(command))
-                        },
-                        move || on_error.clone().emit(()),
+            Msg::UpdateState(mut state) => {
+                if let ViewState::Reveal(_, card) = &mut state {
+                    card.room = self.props.room_id.clone();
+                }
+                if let (ViewState::Blank, Some(members)) = (&state, &self.props.local_members) {
+                    let profiles = Profiles::from_roster(
+                        members
+                            .iter()
+                            .enumerate()
+                            .map(|(index, name)| (index.to_string(), name.clone())),
+                        self.props.your_id.clone(),
                     );
+                    self.dispatch(AppCommand::InitProfile(profiles));
                 }
                 self.state = state
             }
-            Msg::PushCommand(command) => self.runner.dispatch(command),
+            Msg::PushCommand(command) => {
+                self.dispatch(command);
+                if self.props.local_members.is_some() {
+                    self.props.on_dispatch.emit(());
+                }
+            }
+            Msg::Mark(player, hint) => {
+                self.notebook.toggle(player, hint);
+                match serde_json::to_string(&self.notebook) {
+                    Ok(json) => set_notebook(notebook_key(&self.props).as_str(), json.as_str()),
+                    Err(err) => log::error!("{}", err.to_string()),
+                }
+            }
+            Msg::Share(svg) => {
+                share_svg("find-meee-result.svg", svg.as_str(), |err| {
+                    log::error!("Failed to share result: {}", err)
+                });
+                return false;
+            }
         };
         true
     }
@@ -103,13 +140,83 @@ impl Component for Main {
     fn view(&self) -> Html {
         match &self.state {
             ViewState::Blank => loading(),
+            ViewState::Meeting(members, form) => meeting_guest(form, members),
+            ViewState::MeetingHost(members, start) => meeting_host(members, start),
             ViewState::Board(board) => {
                 match board {
-                    model::BoardView::SelectPlacingHint { hints } => html! { <Hand hints=hints.clone()/>},
+                    model::BoardView::SelectPlacingHint { hints, trades, notebook: axes, ability: ability_view, guess, propose } => html! {
+                        <>
+                            <Hand hints=hints.clone()/>
+                            {guess_form(guess)}
+                            {ability_view.as_ref().map_or(html! {}, ability)}
+                            {trade_inbox(trades)}
+                            <ProposeTrade partners=propose.partners.clone() offers=propose.offers.clone() propose=propose.propose.clone()/>
+                            {notebook(&self.notebook_view(axes))}
+                        </>
+                    },
                 }
             },
+            ViewState::Reveal(view, card) => html! {
+                <>
+                    {reveal(view)}
+                    {result_card(card, &self.link.callback(Msg::Share))}
+                </>
+            },
+            ViewState::Spectator(members) => spectator(members),
             ViewState::TODO(json ) => html! {json},
-            ViewState::InputPassword(callback,settings) => html! {<PasswordForm submit=callback hints_num=settings.hints_num/>},
+            ViewState::InputPassword(callback,settings,roles) => html! {
+                <>
+                    {roles.as_ref().map_or(html! {}, role_picker)}
+                    <PasswordForm submit=callback hints_num=settings.hints_num/>
+                </>
+            },
+        }
+    }
+}
+
+/// 一台の端末で遊ぶときは人ごとにメモを分ける
+fn notebook_key(props: &Props) -> String {
+    match props.local_members {
+        Some(_) => format!("{}:{}", props.room_id, props.your_id),
+        None => props.room_id.clone(),
+    }
+}
+
+impl Main {
+    /// 自分が送ったコマンドとして、送った時刻と一緒に記録する
+    fn dispatch(&mut self, command: AppCommand) {
+        self.runner.dispatch(AuthoredCommand {
+            at: Some(now() as u64),
+            ..AuthoredCommand::new(self.props.your_id.clone(), command)
+        })
+    }
+
+    fn notebook_view(&self, axes: &NotebookAxes) -> NotebookView {
+        NotebookView {
+            hints: axes.hints.iter().map(|(_, text)| text.clone()).collect(),
+            rows: axes
+                .players
+                .iter()
+                .map(|(player, name)| NotebookRow {
+                    name: name.clone(),
+                    cells: axes
+                        .hints
+                        .iter()
+                        .map(|(hint, _)| {
+                            let mark = self.notebook.get(player, hint).map(|mark| match mark {
+                                Mark::Confirmed => NoteMark::Confirmed,
+                                Mark::Suspect => NoteMark::Suspect,
+                                Mark::RuledOut => NoteMark::RuledOut,
+                            });
+                            let (player, hint) = (player.clone(), hint.clone());
+                            let toggle = self
+                                .link
+                                .callback(move |_| Msg::Mark(player.clone(), hint.clone()));
+                            (mark, toggle)
+                        })
+                        .collect(),
+                })
+                .collect(),
         }
     }
 }
@@ -1,57 +1,165 @@
+use presentation::{meeting::GuestForm, members::Member};
 use presentation::playing::{
+    ability::AbilityView,
+    guess::GuessView,
     hand::{HandHints, HintType},
     password_form::Form as PasswordForm,
+    result_card::{ResultCard, ResultStanding},
+    reveal::{RevealArrowView, RevealView},
+    roles::RoleOption,
+    trade::{TradeOffer, TradePartner, TradeProposal},
 };
 use yew::prelude::*;
 
 use domain::{
-    function::InitPlayer,
-    model::HintId,
+    function::{is_finished, play_time_secs, ranking, reveal, InitPlayer},
+    model::{Ability, BoardState, GuessOutcome, HintId, Player, PlayerId, Role, TradeId, TradeStatus},
+    profile::Profiles,
     state::{AppCommand, AppState, Setting},
 };
 
 pub enum ViewState {
     Blank,
-    InputPassword(Callback<PasswordForm>, Setting),
+    /// 始める前。ホストには開始ボタンを出す
+    Meeting(Vec<Member>, GuestForm),
+    MeetingHost(Vec<Member>, Callback<()>),
+    /// ホストには役職の選択肢もある
+    InputPassword(Callback<PasswordForm>, Setting, Option<Vec<RoleOption>>),
     Board(BoardView),
+    /// ResultCardのroomはMainが埋める
+    Reveal(RevealView, ResultCard),
+    /// 始まったあとに来た、参加していない人
+    Spectator(Vec<Member>),
     TODO(String),
 }
 
 pub enum BoardView {
-    SelectPlacingHint { hints: HandHints },
+    SelectPlacingHint { hints: HandHints, trades: Vec<TradeOffer>, notebook: NotebookAxes, ability: Option<AbilityView>, guess: GuessView, propose: ProposeTradeView },
+}
+
+pub struct ProposeTradeView {
+    pub partners: Vec<TradePartner>,
+    pub offers: Vec<String>,
+    pub propose: Callback<TradeProposal>,
+}
+
+/// メモの行（自分以外のプレイヤー）と列（手元のヒント）
+pub struct NotebookAxes {
+    pub players: Vec<(PlayerId, String)>,
+    pub hints: Vec<(HintId, String)>,
 }
 
 pub fn app_state_to_view_state(
     app: &AppState,
-    _is_host: bool,
     your_id: &str,
     callback: &Callback<Msg>,
 ) -> ViewState {
     match app {
         AppState::Blank => ViewState::Blank,
+        AppState::Lobby(lobby) => {
+            let members = lobby
+                .members
+                .iter()
+                .map(|member| Member { name: member.name.clone(), you: member.id == your_id })
+                .collect();
+            if lobby.host == your_id {
+                ViewState::MeetingHost(members, callback.reform(|_| Msg::PushCommand(AppCommand::Start)))
+            } else if lobby.contains(your_id) {
+                let leave = callback.reform(|_| Msg::PushCommand(AppCommand::Leave));
+                ViewState::Meeting(members, GuestForm::Joined { leave })
+            } else {
+                let join = callback.reform(|name| Msg::PushCommand(AppCommand::Join { name }));
+                ViewState::Meeting(members, GuestForm::Joinable { join })
+            }
+        }
+        AppState::Board(board, profiles) if is_finished(board) => {
+            ViewState::Reveal(reveal_view(board, profiles), result_card(board, profiles))
+        }
         AppState::Board(board, profiles) => {
-            let profile = profiles.players.get(your_id).expect("TODO");
-            let player = board.players.get(&profile.id).expect("TODO");
+            let (profile, player) = match profiles
+                .players
+                .get(your_id)
+                .and_then(|profile| board.players.get(&profile.id).map(|player| (profile, player)))
+            {
+                Some(found) => found,
+                None => return ViewState::Spectator(roster(profiles)),
+            };
             let get_hint = |id: &HintId| board.hints.get(id).expect("TODO");
             let hints = player
                 .knowledges
                 .others
                 .iter()
                 .map(|hint| (get_hint(hint).text.clone(), HintType::None))
+                .chain(
+                    player
+                        .knowledges
+                        .revealed
+                        .iter()
+                        .map(|hint| (get_hint(hint).text.clone(), HintType::Revealed)),
+                )
                 .chain([(
                     get_hint(&player.knowledges.target).text.clone(),
                     HintType::Target,
                 )])
                 .collect();
-            ViewState::Board(BoardView::SelectPlacingHint { hints })
+            let trades = board
+                .trades
+                .iter()
+                .enumerate()
+                .filter(|(_, trade)| trade.to == profile.id && trade.status == TradeStatus::Pending)
+                .map(|(index, trade)| {
+                    let from = display_name(profiles, &trade.from);
+                    let answer = |accept: bool| {
+                        let id = profile.id.clone();
+                        callback.reform(move |_| {
+                            let trade = TradeId(index);
+                            if accept {
+                                Msg::PushCommand(AppCommand::AcceptTrade(id.clone(), trade))
+                            } else {
+                                Msg::PushCommand(AppCommand::DeclineTrade(id.clone(), trade))
+                            }
+                        })
+                    };
+                    TradeOffer {
+                        from,
+                        offer: get_hint(&trade.offer).text.clone(),
+                        request: get_hint(&trade.request).text.clone(),
+                        accept: answer(true),
+                        decline: answer(false),
+                    }
+                })
+                .collect();
+            let mut players: Vec<_> = profiles
+                .players
+                .values()
+                .filter(|p| p.id != profile.id)
+                .map(|p| (p.id.clone(), p.display_name.clone()))
+                .collect();
+            players.sort_by_key(|(id, _)| id.0);
+            let ability = player.role.as_ref().map(|role| ability_view(role, &profile.id, player, &players, callback));
+            let guess = guess_view(board, &profile.id, player, &players, callback);
+            let propose = propose_trade_view(board, &profile.id, player, &players, callback);
+            let notebook = NotebookAxes {
+                players,
+                hints: std::iter::once(&player.knowledges.target)
+                    .chain(player.knowledges.revealed.iter())
+                    .chain(player.knowledges.others.iter())
+                    .map(|hint| (hint.clone(), get_hint(hint).text.clone()))
+                    .collect(),
+            };
+            ViewState::Board(BoardView::SelectPlacingHint { hints, trades, notebook, ability, guess, propose })
         }
         AppState::StandbyPassword(profiles, inputs, setting) => {
-            let player = profiles.players.get(your_id).expect("TODO");
+            let player = match profiles.players.get(your_id) {
+                Some(player) => player,
+                None => return ViewState::Spectator(roster(profiles)),
+            };
             let complete = inputs.iter().find(|input| input.id == player.id);
             if let Some(complete) = complete {
                 ViewState::TODO(serde_json::to_string(complete).expect("TODO"))
             } else {
                 let id = player.id.clone();
+                let callback_roles = callback;
                 let callback = callback.reform(move |form: PasswordForm| {
                     Msg::PushCommand(AppCommand::PushPassword(InitPlayer {
                         id: id.clone(),
@@ -59,13 +167,184 @@ pub fn app_state_to_view_state(
                         hints: form.hints,
                     }))
                 });
-                ViewState::InputPassword(callback, setting.clone())
+                let roles = if profiles.host == your_id { Some(role_options(&setting.roles, callback_roles)) } else { None };
+                ViewState::InputPassword(callback, setting.clone(), roles)
+            }
+        }
+    }
+}
+
+/// 参加者を参加した順に並べる。観戦している人は含まれない
+fn roster(profiles: &Profiles) -> Vec<Member> {
+    let mut players: Vec<_> = profiles.players.values().collect();
+    players.sort_by_key(|profile| profile.id.0);
+    players
+        .into_iter()
+        .map(|profile| Member { name: profile.display_name.clone(), you: false })
+        .collect()
+}
+
+const ROLES: [Role; 3] = [Role::Detective, Role::Decoy, Role::Guardian];
+
+fn role_label(role: &Role) -> (&'static str, &'static str) {
+    match role {
+        Role::Detective => ("探偵", "ターゲットのひんとを1つ多く知ることができる"),
+        Role::Decoy => ("おとり", "自分のひんとの一部が他の人のものにすり替えられている"),
+        Role::Guardian => ("守り人", "1人を選んで、その人への推理を1度だけ防ぐことができる"),
+    }
+}
+
+fn role_options(selected: &Vec<Role>, callback: &Callback<Msg>) -> Vec<RoleOption> {
+    ROLES
+        .iter()
+        .map(|role| {
+            let (name, description) = role_label(role);
+            let is_selected = selected.contains(role);
+            let roles: Vec<Role> = if is_selected {
+                selected.iter().filter(|r| *r != role).cloned().collect()
+            } else {
+                selected.iter().chain([role]).cloned().collect()
+            };
+            RoleOption {
+                name: name.to_string(),
+                description: description.to_string(),
+                selected: is_selected,
+                toggle: callback.reform(move |_| Msg::PushCommand(AppCommand::ChooseRoles(roles.clone()))),
             }
+        })
+        .collect()
+}
+
+/// 使える能力のボタン。Decoyには使う能力がない
+fn ability_view(role: &Role, id: &PlayerId, player: &Player, players: &Vec<(PlayerId, String)>, callback: &Callback<Msg>) -> AbilityView {
+    let (name, description) = role_label(role);
+    let use_ability = |ability: Ability| {
+        let id = id.clone();
+        callback.reform(move |_| Msg::PushCommand(AppCommand::UseAbility(id.clone(), ability.clone())))
+    };
+    let actions = match role {
+        _ if player.ability_used || player.eliminated => Vec::new(),
+        Role::Detective => vec![("調べる".to_string(), use_ability(Ability::Investigate))],
+        Role::Guardian => std::iter::once(("自分を守る".to_string(), use_ability(Ability::Guard(id.clone()))))
+            .chain(players.iter().map(|(target, name)| (format!("{}を守る", name), use_ability(Ability::Guard(target.clone())))))
+            .collect(),
+        Role::Decoy => Vec::new(),
+    };
+    AbilityView { role: name.to_string(), description: description.to_string(), actions }
+}
+
+fn guess_view(board: &BoardState, id: &PlayerId, player: &Player, players: &Vec<(PlayerId, String)>, callback: &Callback<Msg>) -> GuessView {
+    let status = if player.found {
+        "ターゲットを見つけました".to_string()
+    } else if player.eliminated {
+        "脱落しました".to_string()
+    } else if player.guesses_left == Some(0) {
+        "推理できる回数を使い切りました".to_string()
+    } else if player.cooldown > 0 {
+        format!("あと{}回、他の人が推理するまで推理できません", player.cooldown)
+    } else {
+        match player.guesses_left {
+            Some(left) => format!("あと{}回推理できます", left),
+            None => "何度でも推理できます".to_string(),
+        }
+    };
+    let can_guess = !player.found && !player.eliminated && player.cooldown == 0 && player.guesses_left != Some(0);
+    let last = board.guesses.iter().rev().find(|guess| &guess.guesser == id).map(|guess| {
+        let name = players.iter().find(|(p, _)| p == &guess.suspect).map_or("", |(_, name)| name.as_str());
+        match guess.outcome {
+            GuessOutcome::Found => format!("{}は正解でした", name),
+            GuessOutcome::Wrong => format!("{}ははずれでした", name),
+            GuessOutcome::Blocked => format!("{}への推理は守り人に防がれました", name),
         }
+    });
+    let suspects = if can_guess {
+        players
+            .iter()
+            .map(|(suspect, name)| {
+                let (guesser, suspect) = (id.clone(), suspect.clone());
+                (name.clone(), callback.reform(move |_| Msg::PushCommand(AppCommand::Guess(guesser.clone(), suspect.clone()))))
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    GuessView { status, last, suspects }
+}
+
+/// 相手のひんとは何枚目かだけを選ぶ
+fn propose_trade_view(board: &BoardState, id: &PlayerId, player: &Player, players: &Vec<(PlayerId, String)>, callback: &Callback<Msg>) -> ProposeTradeView {
+    let partners: Vec<(PlayerId, Vec<HintId>)> = players
+        .iter()
+        .map(|(partner, _)| (partner.clone(), board.players.get(partner).map_or(Vec::new(), |p| p.knowledges.others.clone())))
+        .collect();
+    let offers = player.knowledges.others.clone();
+    ProposeTradeView {
+        partners: players
+            .iter()
+            .zip(partners.iter())
+            .map(|((_, name), (_, cards))| TradePartner { name: name.clone(), cards: cards.len() })
+            .collect(),
+        offers: offers
+            .iter()
+            .map(|hint| board.hints.get(hint).map_or(String::new(), |hint| hint.text.clone()))
+            .collect(),
+        propose: {
+            let from = id.clone();
+            callback.reform(move |proposal: TradeProposal| {
+                let (to, cards) = partners[proposal.partner].clone();
+                Msg::PushCommand(AppCommand::ProposeTrade {
+                    from: from.clone(),
+                    to,
+                    offer: offers[proposal.offer].clone(),
+                    request: cards[proposal.card].clone(),
+                })
+            })
+        },
+    }
+}
+
+fn display_name(profiles: &Profiles, id: &PlayerId) -> String {
+    profiles
+        .find(id)
+        .map_or(String::new(), |p| p.display_name.clone())
+}
+
+fn reveal_view(board: &BoardState, profiles: &Profiles) -> RevealView {
+    let arrows = reveal(board);
+    let players: Vec<&PlayerId> = arrows.iter().map(|arrow| &arrow.hunter).collect();
+    let index_of = |id: &PlayerId| players.iter().position(|p| *p == id).expect("TODO");
+    RevealView {
+        players: players.iter().map(|id| display_name(profiles, id)).collect(),
+        arrows: arrows
+            .iter()
+            .map(|arrow| RevealArrowView {
+                hunter: index_of(&arrow.hunter),
+                target: index_of(&arrow.target),
+                hint: board.hints.get(&arrow.hint).expect("TODO").text.clone(),
+                found_at: arrow.found_at,
+            })
+            .collect(),
+    }
+}
+
+fn result_card(board: &BoardState, profiles: &Profiles) -> ResultCard {
+    ResultCard {
+        room: String::new(),
+        standings: ranking(board)
+            .into_iter()
+            .map(|standing| ResultStanding {
+                name: display_name(profiles, &standing.player),
+                score: standing.score,
+                password: standing.password,
+            })
+            .collect(),
+        play_time_secs: play_time_secs(board),
     }
 }
 
 pub enum Msg {
     UpdateState(ViewState),
     PushCommand(AppCommand),
+    Mark(PlayerId, HintId),
+    Share(String),
 }
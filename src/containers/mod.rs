@@ -1,3 +1,3 @@
-pub mod meeting;
 pub mod main;
-pub mod sleeper;
\ No newline at end of file
+pub mod sleeper;
+pub mod local;
\ No newline at end of file
@@ -0,0 +1,125 @@
+use std::{cell::RefCell, rc::Rc};
+
+use js_bridge::{get_local_records, set_local_records};
+use serde::{Deserialize, Serialize};
+use serde_json::Error as SerdeErr;
+use webutil::window::set_timeout;
+
+use exprocess::client::{Record, RecordSync, Repository};
+use domain::{
+    migration::StoredRecord,
+    state::{AppCore, AppResult, AuthoredCommand},
+};
+
+use super::repository::RepositoryError;
+
+type Listener = Box<dyn FnMut(Vec<RecordSync<AppCore>>)>;
+
+/// 一台の端末で遊ぶときに、レコードをlocalStorageに保存するRepository
+pub struct LocalRepository {
+    room_id: String,
+    listener: Rc<RefCell<Option<Listener>>>,
+}
+
+impl LocalRepository {
+    pub fn new(room_id: String) -> Self {
+        Self {
+            room_id,
+            listener: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    fn load(&self) -> Result<Vec<LocalRecordIO>, SerdeErr> {
+        get_local_records(self.room_id.as_str())
+            .map_or(Ok(Vec::new()), |json| serde_json::from_str(json.as_str()))
+    }
+}
+
+/// 保存されたときのバージョンのままのレコード
+#[derive(Serialize, Deserialize)]
+struct LocalRecordIO {
+    id: String,
+    #[serde(flatten)]
+    record: StoredRecord,
+}
+
+struct LocalRecord {
+    id: String,
+    command: AuthoredCommand,
+    result: AppResult,
+}
+
+impl LocalRecordIO {
+    fn upgrade(self) -> Result<LocalRecord, RepositoryError> {
+        let (command, result) = self.record.upgrade()?;
+        Ok(LocalRecord {
+            id: self.id,
+            command,
+            result,
+        })
+    }
+}
+
+/// Firestoreと同じように、pushした処理が終わってから届ける
+fn deliver(listener: &Rc<RefCell<Option<Listener>>>, records: Vec<LocalRecord>) {
+    let listener = listener.clone();
+    set_timeout(
+        move || {
+            if let Some(listener) = listener.borrow_mut().as_mut() {
+                listener(
+                    records
+                        .iter()
+                        .map(|record| RecordSync {
+                            id: record.id.as_str(),
+                            result: &record.result,
+                            command: &record.command,
+                        })
+                        .collect(),
+                );
+            }
+        },
+        0,
+    )
+    .ignore();
+}
+
+impl Repository<AppCore, RepositoryError> for LocalRepository {
+    fn push(&mut self, record: Record<AppCore>, on_error: Box<dyn FnOnce(RepositoryError)>) {
+        let saved = StoredRecord::new(&record.command, &record.result).and_then(|stored| {
+            let mut records = self.load()?;
+            records.push(LocalRecordIO {
+                id: record.id.clone(),
+                record: stored,
+            });
+            serde_json::to_string(&records)
+        });
+        let record = LocalRecord {
+            id: record.id,
+            command: record.command,
+            result: record.result,
+        };
+        match saved {
+            Ok(json) => {
+                set_local_records(self.room_id.as_str(), json.as_str());
+                deliver(&self.listener, vec![record]);
+            }
+            Err(err) => on_error(err.into()),
+        }
+    }
+
+    fn sync(&mut self, listener: Listener, mut on_error: Box<dyn FnMut(RepositoryError)>) {
+        self.listener.replace(Some(listener));
+        let records = self
+            .load()
+            .map_err(RepositoryError::from)
+            .and_then(|records| records.into_iter().map(LocalRecordIO::upgrade).collect());
+        match records {
+            Ok(records) => deliver(&self.listener, records),
+            Err(err) => on_error(err),
+        }
+    }
+
+    fn unsync(&mut self) {
+        self.listener.replace(None);
+    }
+}
@@ -1,10 +1,53 @@
-use domain::state;
+use domain::{checksum::checksum, encoding::RecordEncoding, state::{self, AppCommand, AuthoredCommand}, verify::{VerifiedRepository, VerifyLink}};
+use std::rc::Rc;
+
+use js_bridge::backend::RecordStore;
+use exprocess::{client::{Record, Repository}, core::ExprocessCore};
 pub mod repository;
+pub mod local_repository;
+pub mod server_repository;
 
 pub type Runner = exprocess::client::Runner<state::AppCore,repository::RepositoryError>;
 
-pub fn start(room_id: String,listener: exprocess::client::Listener<state::AppCore,state::AppState>,on_error: Box<dyn FnMut(repository::RepositoryError)>) -> Runner {
-    let repo = repository::AppRepository::new(room_id);
+/// recordsはRoomBackend::recordsで部屋ごとに作る
+pub fn start(records: Rc<dyn RecordStore>,your_id: String,encoding: RecordEncoding,listener: exprocess::client::Listener<state::AppCore,state::AppState>,on_error: Box<dyn FnMut(repository::RepositoryError)>) -> Runner {
+    let verified = records.clone();
+    let link = VerifyLink::default();
+    let repo = VerifiedRepository::new(repository::AppRepository::new(records,your_id,encoding).verified_by(link.clone()))
+        .link(link)
+        .on_verified_snapshot(Box::new(move |state: &state::AppState| verified.set_verified_snapshot(checksum(state).as_str())));
+    let repo = exprocess::directly::DirectlyDispatch::wrap(repo);
+    Runner::start(repo,listener,on_error)
+}
+
+/// 作ったばかりの部屋に、最初のレコードとしてホストの参加を書き込む
+pub fn open_room(records: Rc<dyn RecordStore>,your_id: String,encoding: RecordEncoding,name: String,on_error: Box<dyn FnOnce(repository::RepositoryError)>) {
+    let command = AuthoredCommand::new(your_id.clone(), AppCommand::Join { name });
+    let result = state::AppCore::resolve(&state::AppCore::init(),command.clone());
+    let record = Record { id: format!("{}-join",your_id), command, result };
+    repository::AppRepository::new(records,your_id,encoding).push(record,on_error);
+}
+
+pub fn start_local(room_id: String,listener: exprocess::client::Listener<state::AppCore,state::AppState>,on_error: Box<dyn FnMut(repository::RepositoryError)>) -> Runner {
+    let repo = VerifiedRepository::new(local_repository::LocalRepository::new(room_id));
     let repo = exprocess::directly::DirectlyDispatch::wrap(repo);
     Runner::start(repo,listener,on_error)
+}
+
+/// 部屋を作るときの保存形式の最初の選択。ビルド時にFIND_MEEE_RECORD_ENCODING=compactを指定すると圧縮が選ばれている
+pub fn record_encoding() -> RecordEncoding {
+    match option_env!("FIND_MEEE_RECORD_ENCODING") {
+        Some("compact") => RecordEncoding::Compact,
+        _ => RecordEncoding::Json,
+    }
+}
+
+/// ビルド時にFIND_MEEE_SERVERを指定すると、記録をゲームサーバーで管理する
+pub fn server_url() -> Option<&'static str> {
+    option_env!("FIND_MEEE_SERVER")
+}
+
+pub fn start_server(url: &str,room_id: String,listener: exprocess::client::Listener<state::AppCore,state::AppState>,on_error: Box<dyn FnMut(repository::RepositoryError)>) -> Runner {
+    let repo = server_repository::ServerRepository::new(url.to_string(),room_id);
+    Runner::start(repo,listener,on_error)
 }
\ No newline at end of file
@@ -1,15 +1,40 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::{Cell, RefCell}, convert::TryFrom, rc::Rc};
 
-use js_bridge::{JSFunctionCleaner, RecordPushIO, push_record, sync_record_update};
-use mytil::Cleaner;
+use js_bridge::{BridgeError, RecordBodyIO, RecordPushIO, backend::RecordStore};
+use mytil::{Cleaner, FnOnceCleanable};
 use serde_json::{self,Error as SerdeErr};
 use serde::{Deserialize};
 
-use exprocess::client::{Record, RecordSync, Repository};
-use domain::state::{AppCommand, AppCore, AppResult};
+use exprocess::{client::{Record, RecordSync, Repository}, core::ExprocessCore};
+use domain::{checksum::checksum, encoding::{EncodedRecord, EncodingError, RecordEncoding, encode_compact}, migration::{MigrationError, StoredRecord, schema_version}, sequence::Sequencer, snapshot::{is_snapshot_due, latest_snapshot}, state::{AppCommand, AppCore, AppResult, AppState, AuthoredCommand}, verify::{ReplayMismatch, VerifyLink}};
 
 pub enum RepositoryError {
-    UnExpected
+    UnExpected,
+    /// 記録の読み書きでJS側が失敗した
+    Bridge(BridgeError),
+    /// 記録された結果が手元でresolveした結果と違う
+    Desynced
+}
+
+impl From<ReplayMismatch> for RepositoryError {
+    fn from(mismatch: ReplayMismatch) -> Self {
+        log::error!("Record {} does not match the replayed result",mismatch.record_id);
+        RepositoryError::Desynced
+    }
+}
+
+impl From<MigrationError> for RepositoryError {
+    fn from(err: MigrationError) -> Self {
+        log::error!("{}",err);
+        RepositoryError::UnExpected
+    }
+}
+
+impl From<EncodingError> for RepositoryError {
+    fn from(err: EncodingError) -> Self {
+        log::error!("{}",err);
+        RepositoryError::UnExpected
+    }
 }
 
 impl From<SerdeErr> for RepositoryError {
@@ -19,73 +44,387 @@ impl From<SerdeErr> for RepositoryError {
     }
 }
 
+/// 同期済みのレコードを畳み込んだ状態と、次に追記するseq_no
+struct Appender {
+    records: Rc<dyn RecordStore>,
+    /// スナップショットを残すときの送り主
+    member_id: String,
+    encoding: RecordEncoding,
+    sequencer: Sequencer<RecordDesirailizeIO>,
+    state: AppState,
+    /// 追記が競合して、新しいレコードが届くのを待っているもの
+    conflicted: Vec<Conflicted>,
+    /// 書き換えられたレコードが見つかったら、そこで止まる
+    verify: VerifyLink
+}
+
+struct Conflicted {
+    id: String,
+    command: AuthoredCommand,
+    /// 競合したときに期待していたseq_no
+    seq_no: usize,
+    on_error: Box<dyn FnOnce(RepositoryError)>
+}
+
 pub struct AppRepository {
-    room_id: String,
-    unsync_fn: JSFunctionCleaner
+    appender: Rc<RefCell<Appender>>,
+    unsync_fn: Cleaner<FnOnceCleanable>
 }
 
 impl AppRepository {
-    pub fn new(room_id: String) -> Self {
+    pub fn new(records: Rc<dyn RecordStore>,member_id: String,encoding: RecordEncoding) -> Self {
         Self {
-            room_id,
+            appender: Rc::new(RefCell::new(Appender {
+                records,
+                member_id,
+                encoding,
+                sequencer: Sequencer::default(),
+                state: AppCore::init(),
+                conflicted: Vec::new(),
+                verify: VerifyLink::default()
+            })),
             unsync_fn: Cleaner::empty()
         }
     }
+
+    /// VerifiedRepositoryに包むときに、同じVerifyLinkを渡す
+    pub fn verified_by(self,link: VerifyLink) -> Self {
+        self.appender.borrow_mut().verify = link;
+        self
+    }
 }
 
 #[derive(Deserialize)]
+#[serde(try_from = "RecordRawIO")]
 pub struct RecordDesirailizeIO {
+    pub id: String,
+    pub seq_no: usize,
+    pub command: AuthoredCommand,
+    pub result: AppResult,
+    /// 書き込んだクライアントで、このレコードまで反映した状態のchecksum
+    pub checksum: Option<String>
+}
+
+/// 保存されたときの形式とバージョンのままのレコード
+#[derive(Deserialize)]
+struct RecordRawIO {
     id: String,
-    command: AppCommand,
-    result: AppResult
+    seq_no: usize,
+    #[serde(default)]
+    checksum: Option<String>,
+    #[serde(flatten)]
+    record: EncodedRecord
 }
 
-impl Repository<AppCore,RepositoryError> for AppRepository {
+impl TryFrom<RecordRawIO> for RecordDesirailizeIO {
+    type Error = MigrationError;
 
-    fn push(&mut self,record: Record<AppCore>,on_error: Box<dyn FnOnce(RepositoryError)>) {
+    fn try_from(raw: RecordRawIO) -> Result<Self,Self::Error> {
+        let (command,result) = raw.record.decode()?.upgrade()?;
+        Ok(Self { id: raw.id, seq_no: raw.seq_no, command, result, checksum: raw.checksum })
+    }
+}
+
+/// 届いたレコードのJSONを読む。形が違うものや読めない形式のものはBridgeError::Malformedにする
+pub fn decode_records(json: &str) -> Result<Vec<RecordDesirailizeIO>,RepositoryError> {
+    serde_json::from_str(json).map_err(|err| {
+        log::error!("Malformed records: {}",err);
+        RepositoryError::Bridge(BridgeError::Malformed)
+    })
+}
+
+/// 同期で届いたレコードを、seq_no順に抜けなく反映する
+struct Receiver {
+    appender: Rc<RefCell<Appender>>,
+    listener: RefCell<Box<dyn FnMut(Vec<RecordSync<AppCore>>)>>,
+    on_error: RefCell<Box<dyn FnMut(RepositoryError)>>,
+    /// 抜けたレコードを取り直している
+    fetching: Cell<bool>
+}
+
+fn receive(receiver: &Rc<Receiver>,json: String) {
+    if receiver.appender.borrow().verify.is_halted() {
+        return;
+    }
+    let records = match decode_records(json.as_str()) {
+        Ok(records) => records,
+        Err(err) => return (receiver.on_error.borrow_mut())(err),
+    };
+    let (ready,gap,records,next,desynced) = {
+        let mut appender = receiver.appender.borrow_mut();
+        // 最初の同期は、以前に最初から辿って確かめた最新のスナップショットから始める。
+        // 確かめていなければ抜けとして最初から取り直す
+        if appender.sequencer.next() == 0 {
+            if let Some(seq_no) = latest_snapshot(records.iter().map(|record| (record.seq_no,&record.result))) {
+                let verified = appender.records.verified_snapshot();
+                let trusted = records.iter().any(|record| record.seq_no == seq_no && match &record.result {
+                    AppResult::Snapshot(state) => verified.as_deref() == Some(checksum(state).as_str()),
+                    _ => false,
+                });
+                if trusted {
+                    appender.sequencer.start_at(seq_no);
+                    appender.verify.start_at_verified_snapshot();
+                }
+            }
+        }
+        let ready = appender.sequencer.receive(records.into_iter().map(|record| (record.seq_no,record)));
+        let mut desynced = false;
+        for record in ready.iter() {
+            AppCore::reducer(&mut appender.state,record.result.clone());
+            // 書き込んだクライアントと違う状態になっていないか
+            if let Some(expected) = &record.checksum {
+                if &checksum(&appender.state) != expected {
+                    log::error!("State after record {} does not match its checksum",record.id);
+                    desynced = true;
+                }
+            }
+        }
+        // 競合と判定されても書き込まれていることがある
+        appender.conflicted.retain(|conflicted| !ready.iter().any(|record| record.id == conflicted.id));
+        (ready,appender.sequencer.has_gap(),appender.records.clone(),appender.sequencer.next(),desynced)
+    };
+    if desynced {
+        (receiver.on_error.borrow_mut())(RepositoryError::Desynced);
+    }
+    if !ready.is_empty() {
+        (receiver.listener.borrow_mut())(
+            ready.iter()
+            .map(|record| RecordSync {id: record.id.as_str(), result: &record.result, command: &record.command})
+            .collect()
+        );
+        // 届いたものが書き換えられていたら、その状態で追記しない
+        if receiver.appender.borrow().verify.is_halted() {
+            return;
+        }
+        retry(&receiver.appender);
+        let taken = matches!(ready.last(),Some(RecordDesirailizeIO { result: AppResult::Snapshot(_), .. }));
+        if !gap && !taken && is_snapshot_due(next) {
+            take_snapshot(&receiver.appender,next);
+        }
+    }
+    if gap && !receiver.fetching.replace(true) {
+        log::warn!("Record {} is missing. Refetching",next);
+        let on_fetched = receiver.clone();
+        let on_error = receiver.clone();
+        records.fetch(
+            next,
+            Box::new(move |json| {
+                on_fetched.fetching.set(false);
+                receive(&on_fetched,json);
+            }),
+            Box::new(move |err| (on_error.on_error.borrow_mut())(RepositoryError::Bridge(err)))
+        );
+    }
+}
+
+/// 部屋の保存形式で書き出したcommandとresult
+enum EncodedBody {
+    Json { command: String, result: String },
+    Compact { payload: String }
+}
 
-        match (serde_json::to_string(&record.result),serde_json::to_string(&record.command)) {
-            (Ok(result_json), Ok(command_json)) => {
-                let io = RecordPushIO {
-                    id: record.id.as_str(),
-                    result: result_json.as_str(),
-                    command: command_json.as_str()
-                };
-                push_record(
-                    self.room_id.as_str(),
-                    io,
-                    || on_error(RepositoryError::UnExpected)
-                );
+impl EncodedBody {
+    fn encode(encoding: RecordEncoding,record: &Record<AppCore>) -> Result<Self,RepositoryError> {
+        Ok(match encoding {
+            RecordEncoding::Json => EncodedBody::Json {
+                command: serde_json::to_string(&record.command.command)?,
+                result: serde_json::to_string(&record.result)?
             },
-            (_, Err(err)) => on_error(err.into()),
-            (Err(err), _) => on_error(err.into()),
+            RecordEncoding::Compact => EncodedBody::Compact {
+                payload: encode_compact(&StoredRecord::new(&record.command,&record.result)?)?
+            },
+        })
+    }
+
+    fn as_io(&self) -> RecordBodyIO {
+        match self {
+            EncodedBody::Json { command, result } => RecordBodyIO::Json { command: command.as_str(), result: result.as_str() },
+            EncodedBody::Compact { payload } => RecordBodyIO::Compact { payload: payload.as_str() },
         }
     }
+}
 
-    fn sync(&mut self,mut listener: Box<dyn FnMut(Vec<RecordSync<AppCore>>)>,on_error: Box<dyn FnMut(RepositoryError)>) {
-        let on_error = Rc::new(RefCell::new(on_error));
-        let on_error_callback = on_error.clone();
-        self.unsync_fn = sync_record_update(
-            self.room_id.as_str(), 
-            move |json| {
-            let result : Result<Vec<RecordDesirailizeIO>,_> = serde_json::from_str(&json);
-            match result {
-                Ok(records) => {
-                    listener(
-                        records.iter()
-                        .map(|record| RecordSync {id: record.id.as_str(), result: &record.result, command: &record.command}) 
-                        .collect()
-                    );
-                },
-                Err(err) => on_error_callback.borrow_mut()(err.into()),
-            }
+/// 同期済みの次のseq_noとして追記する
+fn append(appender: &Rc<RefCell<Appender>>,record: Record<AppCore>,on_error: Box<dyn FnOnce(RepositoryError)>) {
+    if appender.borrow().verify.is_halted() {
+        return on_error(RepositoryError::Desynced);
+    }
+    let (records,seq_no,encoding,state_checksum) = {
+        let appender = appender.borrow();
+        let mut state = appender.state.clone();
+        AppCore::reducer(&mut state,record.result.clone());
+        (appender.records.clone(),appender.sequencer.next(),appender.encoding,checksum(&state))
+    };
+    match EncodedBody::encode(encoding,&record) {
+        Ok(body) => {
+            let io = RecordPushIO {
+                id: record.id.as_str(),
+                author: record.command.author.as_str(),
+                at: record.command.at,
+                body: body.as_io(),
+                seq_no,
+                checksum: state_checksum.as_str(),
+                version: schema_version(&record.command),
+                snapshot: matches!(record.result,AppResult::Snapshot(_))
+            };
+            let appender = appender.clone();
+            let (id,command) = (record.id.clone(),record.command.clone());
+            records.push(
+                io,
+                Box::new(move |err| match err {
+                    // 他の誰かが先に進めたなら、スナップショットはその先で残せばよい
+                    BridgeError::Conflict if matches!(command.command,AppCommand::TakeSnapshot) => {},
+                    BridgeError::Conflict => {
+                        appender.borrow_mut().conflicted.push(Conflicted { id, command, seq_no, on_error });
+                        retry(&appender);
+                    },
+                    err => on_error(RepositoryError::Bridge(err)),
+                })
+            );
         },
-        move || on_error.borrow_mut()(RepositoryError::UnExpected)
-    );
+        Err(err) => on_error(err),
+    }
+}
+
+/// 同期済みの状態をスナップショットとして追記する
+fn take_snapshot(appender: &Rc<RefCell<Appender>>,seq_no: usize) {
+    let record = {
+        let appender = appender.borrow();
+        let command = AuthoredCommand::new(appender.member_id.clone(), AppCommand::TakeSnapshot);
+        let result = AppCore::resolve(&appender.state,command.clone());
+        // 拒否されたなら、残すものは無い
+        if !matches!(result,AppResult::Snapshot(_)) {
+            return;
+        }
+        Record { id: format!("snapshot-{}",seq_no), command, result }
+    };
+    append(appender,record,Box::new(|_| log::warn!("Failed to take a snapshot")));
+}
+
+/// 競合したあとに新しいレコードが届いていれば、その状態でresolveし直して送る
+fn retry(appender: &Rc<RefCell<Appender>>) {
+    let next = {
+        let mut appender = appender.borrow_mut();
+        let seq_no = appender.sequencer.next();
+        match appender.conflicted.iter().position(|conflicted| conflicted.seq_no < seq_no) {
+            Some(index) => {
+                let Conflicted { id, command, on_error, .. } = appender.conflicted.remove(index);
+                let result = AppCore::resolve(&appender.state,command.clone());
+                Some((Record { id, command, result },on_error))
+            },
+            None => None,
+        }
+    };
+    if let Some((record,on_error)) = next {
+        append(appender,record,on_error);
+    }
+}
+
+impl Repository<AppCore,RepositoryError> for AppRepository {
+
+    fn push(&mut self,record: Record<AppCore>,on_error: Box<dyn FnOnce(RepositoryError)>) {
+        append(&self.appender,record,on_error);
+    }
+
+    fn sync(&mut self,listener: Box<dyn FnMut(Vec<RecordSync<AppCore>>)>,on_error: Box<dyn FnMut(RepositoryError)>) {
+        let receiver = Rc::new(Receiver {
+            appender: self.appender.clone(),
+            listener: RefCell::new(listener),
+            on_error: RefCell::new(on_error),
+            fetching: Cell::new(false)
+        });
+        let on_error = receiver.clone();
+        let records = self.appender.borrow().records.clone();
+        self.unsync_fn = records.sync(
+            Box::new(move |json| receive(&receiver,json)),
+            Box::new(move |err| (on_error.on_error.borrow_mut())(RepositoryError::Bridge(err)))
+        );
     }
 
     fn unsync(&mut self) {
         self.unsync_fn.clean();
     }
 }
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use domain::{encoding::{RecordEncoding, encode_compact}, migration::StoredRecord, state::{AppCommand, AppCore, AppResult, AppState, AuthoredCommand}, verify::{VerifiedRepository, VerifyLink}};
+    use exprocess::{client::{Record, Repository}, core::ExprocessCore};
+    use js_bridge::{BridgeError, backend::{MemoryBackend, RoomBackend}};
+
+    use super::{decode_records, AppRepository, RepositoryError};
 
+    const JSON_RECORD: &str = r#"[{"id":"leave","seq_no":3,"checksum":"0","version":2,"command":{"author":"member-1","command":{"type":"Leave"}},"result":{"type":"Left","value":"member-1"}}]"#;
+
+    #[test]
+    fn test_decode_records() {
+        let records = decode_records(JSON_RECORD).ok().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].seq_no, 3);
+        assert!(matches!(records[0].result, AppResult::Left(_)));
+        let command = AuthoredCommand::new("member-1".to_string(), AppCommand::Leave);
+        let payload = encode_compact(&StoredRecord::new(&command, &AppResult::Left("member-1".to_string())).unwrap()).unwrap();
+        let json = format!(r#"[{{"id":"leave","seq_no":3,"payload":"{}"}}]"#, payload);
+        assert!(matches!(decode_records(json.as_str()).ok().unwrap()[0].command.command, AppCommand::Leave));
+    }
+
+    #[test]
+    fn test_reject_malformed_records() {
+        for json in [
+            "",
+            "{",
+            "null",
+            r#"{"id":"leave","seq_no":3}"#,
+            r#"[{"id":"leave","seq_no":3}]"#,
+            r#"[{"id":"leave","seq_no":"3","version":2,"command":{"author":"member-1","command":{"type":"Leave"}},"result":{"type":"Left","value":"member-1"}}]"#,
+            r#"[{"id":"leave","seq_no":3,"version":2,"command":{"author":"member-1","command":{"type":"Fly"}},"result":{"type":"Left","value":"member-1"}}]"#,
+            r#"[{"id":"leave","seq_no":3,"version":99,"command":{"author":"member-1","command":{"type":"Leave"}},"result":{"type":"Left","value":"member-1"}}]"#,
+            r#"[{"id":"leave","seq_no":3,"payload":"not base64"}]"#,
+        ] {
+            assert!(matches!(decode_records(json), Err(RepositoryError::Bridge(BridgeError::Malformed))), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_stop_after_forged_result() {
+        let backend = MemoryBackend::default();
+        let join = |member: &str| AuthoredCommand::new(member.to_string(), AppCommand::Join { name: member.to_string() });
+        let record = |id: &str, command: AuthoredCommand, result: AppResult| Record { id: id.to_string(), command, result };
+        let mut host = AppRepository::new(backend.records("room"), "member-0".to_string(), RecordEncoding::Json);
+        host.sync(Box::new(|_| {}), Box::new(|_| panic!()));
+        let result = AppCore::resolve(&AppCore::init(), join("member-0"));
+        host.push(record("host", join("member-0"), result), Box::new(|_| panic!()));
+        // resolveし直すと参加になるはずの結果を、退出に書き換えて送る
+        host.push(record("forged", join("member-1"), AppResult::Left("member-0".to_string())), Box::new(|_| panic!()));
+        host.unsync();
+
+        let link = VerifyLink::default();
+        let states = Rc::new(RefCell::new(Vec::new()));
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let (on_records, on_error) = (states.clone(), errors.clone());
+        let mut state = AppCore::init();
+        let mut repo = VerifiedRepository::new(AppRepository::new(backend.records("room"), "member-2".to_string(), RecordEncoding::Json).verified_by(link.clone()))
+            .link(link.clone());
+        repo.sync(
+            Box::new(move |records| {
+                for record in records {
+                    AppCore::reducer(&mut state, record.result.clone());
+                }
+                on_records.borrow_mut().push(state.clone());
+            }),
+            Box::new(move |err| on_error.borrow_mut().push(err)),
+        );
+        assert!(link.is_halted());
+        assert!(matches!(states.borrow().as_slice(), [AppState::Lobby(lobby)] if lobby.members.len() == 1));
+        assert!(matches!(errors.borrow().as_slice(), [RepositoryError::Desynced]));
+        // 書き換えられた状態のまま追記しない
+        let result = AppCore::resolve(&AppCore::init(), join("member-2"));
+        repo.push(record("late", join("member-2"), result), Box::new(move |err| errors.borrow_mut().push(err)));
+        repo.unsync();
+        let fetched = Rc::new(RefCell::new(String::new()));
+        let on_fetched = fetched.clone();
+        backend.records("room").fetch(0, Box::new(move |json| *on_fetched.borrow_mut() = json), Box::new(|_| panic!()));
+        assert_eq!(decode_records(fetched.borrow().as_str()).ok().unwrap().len(), 2);
+    }
+}
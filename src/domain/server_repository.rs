@@ -0,0 +1,68 @@
+use std::{cell::RefCell, rc::Rc};
+
+use js_bridge::{JSFunctionCleaner, push_server_command, sync_server_record};
+use mytil::Cleaner;
+
+use exprocess::client::{Record, RecordSync, Repository};
+use domain::state::AppCore;
+
+use super::repository::{decode_records, RepositoryError};
+
+/// ゲームサーバーにコマンドだけを送り、サーバーが決めた結果を受け取るRepository
+pub struct ServerRepository {
+    url: String,
+    room_id: String,
+    unsync_fn: JSFunctionCleaner
+}
+
+impl ServerRepository {
+    pub fn new(url: String,room_id: String) -> Self {
+        Self {
+            url,
+            room_id,
+            unsync_fn: Cleaner::empty()
+        }
+    }
+}
+
+impl Repository<AppCore,RepositoryError> for ServerRepository {
+
+    /// 手元でresolveした結果は使わない
+    fn push(&mut self,record: Record<AppCore>,on_error: Box<dyn FnOnce(RepositoryError)>) {
+        match serde_json::to_string(&record.command) {
+            Ok(command_json) => push_server_command(
+                self.room_id.as_str(),
+                record.id.as_str(),
+                command_json.as_str(),
+                |err| on_error(RepositoryError::Bridge(err))
+            ),
+            Err(err) => on_error(err.into()),
+        }
+    }
+
+    fn sync(&mut self,mut listener: Box<dyn FnMut(Vec<RecordSync<AppCore>>)>,on_error: Box<dyn FnMut(RepositoryError)>) {
+        let on_error = Rc::new(RefCell::new(on_error));
+        let on_error_callback = on_error.clone();
+        self.unsync_fn = sync_server_record(
+            self.url.as_str(),
+            self.room_id.as_str(),
+            move |json| {
+                match decode_records(json.as_str()) {
+                    Ok(records) => {
+                        listener(
+                            records.iter()
+                            .map(|record| RecordSync {id: record.id.as_str(), result: &record.result, command: &record.command})
+                            .collect()
+                        );
+                    },
+                    Err(err) => on_error_callback.borrow_mut()(err),
+                }
+            },
+            move |err| on_error.borrow_mut()(RepositoryError::Bridge(err))
+        );
+    }
+
+    fn unsync(&mut self) {
+        self.unsync_fn.clean();
+    }
+}
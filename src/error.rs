@@ -0,0 +1,58 @@
+use js_bridge::BridgeError;
+
+use crate::domain::repository::RepositoryError;
+
+/// 画面を止めて知らせる失敗
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum AppError {
+    Bridge(BridgeError),
+    /// 記録された結果が手元で組み立てた状態と合わない
+    Desynced,
+    Unexpected
+}
+
+/// 失敗から戻る方法
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Recovery {
+    /// 同じ画面を開き直す
+    Retry,
+    GoHome
+}
+
+impl From<BridgeError> for AppError {
+    fn from(err: BridgeError) -> Self {
+        AppError::Bridge(err)
+    }
+}
+
+impl From<RepositoryError> for AppError {
+    fn from(err: RepositoryError) -> Self {
+        match err {
+            RepositoryError::UnExpected => AppError::Unexpected,
+            RepositoryError::Bridge(err) => AppError::Bridge(err),
+            RepositoryError::Desynced => AppError::Desynced,
+        }
+    }
+}
+
+impl AppError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            AppError::Bridge(BridgeError::PermissionDenied) => "この部屋に入る権限がありません。",
+            AppError::Bridge(BridgeError::NotFound) => "部屋が見つかりませんでした。",
+            AppError::Bridge(BridgeError::Network) => "通信できませんでした。接続を確かめてからもう一度お試しください。",
+            AppError::Bridge(BridgeError::Conflict) => "ほかの人の操作と重なりました。もう一度お試しください。",
+            AppError::Bridge(BridgeError::Quota) => "混み合っています。しばらくしてからお試しください。",
+            AppError::Bridge(BridgeError::Malformed) => "部屋のデータを読み込めませんでした。",
+            AppError::Desynced => "ほかの人と盤面が食い違いました。読み込み直してください。",
+            AppError::Bridge(BridgeError::Unexpected) | AppError::Unexpected => "予期しないエラーが起きました。",
+        }
+    }
+
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            AppError::Bridge(BridgeError::PermissionDenied | BridgeError::NotFound) => Recovery::GoHome,
+            _ => Recovery::Retry,
+        }
+    }
+}
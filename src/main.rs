@@ -1,31 +1,40 @@
+use std::rc::Rc;
+
+use js_bridge::backend::{FirestoreBackend, RoomBackend};
 use presentation::layout::layout;
 use wasm_bindgen::prelude::*;
 
 use yew::prelude::*;
+use yew_router::{agent::RouteRequest, prelude::RouteAgentDispatcher};
 
 mod containers;
 mod domain;
+mod error;
 mod pages;
 mod routing;
 
 use pages::{home::Home, room::Room};
 
-use presentation::{error};
+use presentation::error::error as error_view;
 
 use crate::routing::{AppRoute, AppRouter};
 use crate::containers::sleeper::Sleeper;
+use crate::containers::local::LocalGame;
+use crate::error::{AppError, Recovery};
 
 pub enum Msg {
-    Error,
+    Error(AppError),
+    Recover,
 }
 
 pub enum State {
-    Error,
+    Error(AppError),
     Ok,
 }
 
 pub struct App {
     state: State,
+    backend: Rc<dyn RoomBackend>,
     link: ComponentLink<Self>
 }
 impl Component for App {
@@ -35,14 +44,27 @@ impl Component for App {
     fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
             state: State::Ok,
+            backend: Rc::new(FirestoreBackend),
             link,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::Error => {
-                self.state = State::Error;
+            Msg::Error(err) => {
+                log::error!("{:?}", err);
+                self.state = State::Error(err);
+                true
+            }
+            Msg::Recover => {
+                if let State::Error(err) = &self.state {
+                    if err.recovery() == Recovery::GoHome {
+                        let mut dispatcher: RouteAgentDispatcher<()> = RouteAgentDispatcher::new();
+                        dispatcher.send(RouteRequest::ChangeRoute(AppRoute::Home.into()));
+                    }
+                }
+                // 作り直して、同期からやり直す
+                self.state = State::Ok;
                 true
             }
         }
@@ -54,21 +76,36 @@ impl Component for App {
 
     fn view(&self) -> Html {
         layout(match self.state {
-            State::Error => error::error(),
+            State::Error(err) => error_view(
+                err.message(),
+                match err.recovery() {
+                    Recovery::Retry => "もう一度試す",
+                    Recovery::GoHome => "トップに戻る",
+                },
+                &self.link.callback(|_| Msg::Recover),
+            ),
             State::Ok => {
                 let link = self.link.clone();
+                let backend = self.backend.clone();
                 let render = AppRouter::render(move |switch: AppRoute| {
-                    let on_error = link.callback(|_| Msg::Error);
+                    let on_error = link.callback(Msg::Error);
                     match switch {
                         AppRoute::Home => {
                             html! { 
-                                <Home on_error=on_error/> 
+                                <Home backend=backend.clone() on_error=on_error/> 
+                            }
+                        }
+                        AppRoute::Local => {
+                            html! {
+                                <Sleeper>
+                                    <LocalGame backend=backend.clone() on_error=on_error/>
+                                </Sleeper>
                             }
                         }
                         AppRoute::Room(room_id) => {
                             html! { 
                                 <Sleeper>
-                                    <Room room_id=room_id on_error=on_error/> 
+                                    <Room backend=backend.clone() room_id=room_id on_error=on_error/> 
                                 </Sleeper>
                             }
                         }
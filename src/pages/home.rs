@@ -1,8 +1,11 @@
-use presentation::{home::home};
+use std::rc::Rc;
+
+use presentation::home::{home, OpenRoom};
 use yew::prelude::*;
 use yew_router::{agent::RouteRequest, prelude::*};
-use crate::{routing::AppRoute};
-use js_bridge::{create_room};
+use crate::{domain::{open_room, record_encoding, repository::RepositoryError}, error::AppError, routing::AppRoute};
+use domain::encoding::RecordEncoding;
+use js_bridge::{backend::RoomBackend, BridgeError};
 
 pub struct Home {
     state: State,
@@ -11,17 +14,18 @@ pub struct Home {
 
 pub enum State {
     Init {
-        on_submit: Callback<String>
+        on_submit: Callback<OpenRoom>
     },
 }
 
 pub enum Msg {
-    CreateRoom(String)
+    CreateRoom(OpenRoom)
 }
 
 #[derive(Properties,Clone)]
 pub struct Props {
-    pub on_error: Callback<()>
+    pub backend: Rc<dyn RoomBackend>,
+    pub on_error: Callback<AppError>
 }
 impl Component for Home {
     type Message = Msg;
@@ -38,12 +42,19 @@ impl Component for Home {
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::CreateRoom(name) => {
+            Msg::CreateRoom(OpenRoom { name, compact_records }) => {
                 let on_error = self.props.on_error.clone();
-                let id = create_room(
-                    &name,
-                    || {},
-                    move || on_error.clone().emit(())
+                let on_push_error = self.props.on_error.clone();
+                let encoding = if compact_records { RecordEncoding::Compact } else { RecordEncoding::Json };
+                // 部屋ができてから、最初に参加した人としてホストになる
+                let backend = self.props.backend.clone();
+                let id = self.props.backend.create_room(
+                    encoding == RecordEncoding::Compact,
+                    Box::new(move |room_id| {
+                        let your_id = backend.your_id(room_id.as_str());
+                        open_room(backend.records(room_id.as_str()), your_id, encoding, name, Box::new(move |err: RepositoryError| on_push_error.emit(err.into())));
+                    }),
+                    Box::new(move |err: BridgeError| on_error.emit(err.into()))
                 );
                 let route = AppRoute::Room(id);
                 let mut dispatcher: RouteAgentDispatcher<()> = RouteAgentDispatcher::new();
@@ -59,7 +70,7 @@ impl Component for Home {
 
     fn view(&self) -> Html {
         match &self.state {
-            State::Init { on_submit } => home(on_submit),
+            State::Init { on_submit } => home(on_submit, record_encoding() == RecordEncoding::Compact),
         }
         
     }
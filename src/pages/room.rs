@@ -1,18 +1,19 @@
+use std::rc::Rc;
 
+use mytil::{Cleaner, FnOnceCleanable};
 use presentation::loading::loading;
 use presentation::not_found::not_found;
 use yew::prelude::*;
-use crate::containers::main::Main;
-use js_bridge::{sync_room,Room as RoomData,Phase,start_room,get_your_id,JSFunctionCleaner};
-use crate::containers::meeting::{Meeting,MeetingHost};
+use crate::{containers::main::Main, error::AppError};
+use domain::encoding::RecordEncoding;
+use js_bridge::{Room as RoomData,BridgeError,backend::RoomBackend};
 pub struct Room {
     state: State,
     props: Props,
-    link: ComponentLink<Self>,
-    on_destroy: JSFunctionCleaner
+    on_destroy: Cleaner<FnOnceCleanable>
 }
 
-type YourId = Option<String>;
+type YourId = String;
 enum State {
     Loading,
     Fetched(RoomData,YourId),
@@ -21,14 +22,14 @@ enum State {
 
 pub enum Msg {
     UpdateRoom(RoomData),
-    RoomNotExists,
-    Start
+    RoomNotExists
 }
 
-#[derive(Clone, Debug, Properties)]
+#[derive(Clone, Properties)]
 pub struct Props {
+    pub backend: Rc<dyn RoomBackend>,
     pub room_id: String,
-    pub on_error: Callback<()>
+    pub on_error: Callback<AppError>
 }
 
 impl Component for Room {
@@ -37,13 +38,12 @@ impl Component for Room {
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::UpdateRoom(room) => self.state = State::Fetched(room,get_your_id(self.props.room_id.as_str())),
+            // 参加や開始はMainがレコードから組み立てるので、部屋が見つかれば一度だけ描けばよい
+            Msg::UpdateRoom(room) => match self.state {
+                State::Fetched(_,_) => return false,
+                _ => self.state = State::Fetched(room,self.props.backend.your_id(self.props.room_id.as_str())),
+            },
             Msg::RoomNotExists => self.state = State::NotExists,
-            Msg::Start => {
-                self.state = State::Loading;
-                let on_error = self.props.on_error.clone();
-                start_room(&self.props.room_id,move || on_error.clone().emit(()));
-            }
         };
         true
     }
@@ -59,27 +59,15 @@ impl Component for Room {
     fn view(&self) -> Html {
         match &self.state {
             State::Loading => loading(),
-            State::Fetched(room,your_id) => match room.phase {
-                Phase::Meeting =>  if !room.is_host {
-                    html! {<Meeting room_id=self.props.room_id.clone() on_error=self.props.on_error.clone()/>}
-                } else {
-                    let start = self.link.callback(|_| Msg::Start);
-                    html! {<MeetingHost room_id=self.props.room_id.clone() start=start on_error=self.props.on_error.clone()/>}
-                },
-                Phase::Started => match your_id {
-                    Some(your_id) => html! {
-                        <Main 
-                            is_host=room.is_host 
-                            room_id=self.props.room_id.clone()
-                            your_id=your_id.clone()
-                            on_error=self.props.on_error.clone()
-                        />
-                    },
-                    None => not_found(),
-                }
-                
-                
-            }
+            State::Fetched(room,your_id) => html! {
+                <Main 
+                    backend=self.props.backend.clone()
+                    room_id=self.props.room_id.clone()
+                    your_id=your_id.clone()
+                    on_error=self.props.on_error.clone()
+                    record_encoding=if room.compact_records { RecordEncoding::Compact } else { RecordEncoding::Json }
+                />
+            },
             State::NotExists => not_found(),
         }
     }
@@ -89,16 +77,15 @@ impl Component for Room {
              |room : Option<RoomData>| room.map_or(Msg::RoomNotExists, Msg::UpdateRoom)
         );
         let on_error = props.on_error.clone();
-        let on_destroy = sync_room(
+        let on_destroy = props.backend.sync_room(
             room_id, 
-            move |room| callback.emit(room),
-            move || on_error.clone().emit(())
+            Box::new(move |room| callback.emit(room)),
+            Box::new(move |err: BridgeError| on_error.emit(err.into()))
         );
         Self {
             state: State::Loading,
             props,
-            link,
             on_destroy
         }
     }
-}
\ No newline at end of file
+}
@@ -4,6 +4,8 @@ use yew_router::{Switch, router::Router};
 pub enum AppRoute {
     #[to = "/!"]
     Home,
+    #[to = "/local!"]
+    Local,
     #[to = "/{*:id}"]
     Room(String),
 }